@@ -0,0 +1,98 @@
+//! Comparison of the found versions against a checked-in list of expected versions.
+
+use anyhow::{Context, Result as Fallible};
+use semver::Version;
+use std::collections::{BTreeSet, HashSet};
+use std::path::Path;
+
+/// Drop the architecture suffix, so `4.2.14+amd64` and `4.2.14` compare equal.
+fn normalize(version: &Version) -> Version {
+    let mut version = version.clone();
+    version.build.clear();
+    version
+}
+
+/// Read a baseline file holding one version per line; blank lines and `#` comments are ignored.
+pub async fn load(path: &Path) -> Fallible<BTreeSet<Version>> {
+    let text = tokio::fs::read_to_string(path)
+        .await
+        .context(format!("Reading baseline {:?}", path))?;
+    let mut versions = BTreeSet::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let version = Version::parse(line).context(format!("Parsing '{}' in {:?}", line, path))?;
+        versions.insert(normalize(&version));
+    }
+    Ok(versions)
+}
+
+/// Versions which differ between the baseline and the graph data.
+#[derive(Debug, Default)]
+pub struct Diff {
+    /// In the graph data but not in the baseline.
+    pub added: Vec<Version>,
+    /// In the baseline but no longer in the graph data.
+    pub removed: Vec<Version>,
+}
+
+pub fn diff(baseline: &BTreeSet<Version>, found_versions: &HashSet<Version>) -> Diff {
+    let found: BTreeSet<Version> = found_versions.iter().map(normalize).collect();
+    Diff {
+        added: found.difference(baseline).cloned().collect(),
+        removed: baseline.difference(&found).cloned().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(versions: &[&str]) -> Vec<Version> {
+        versions
+            .iter()
+            .map(|v| Version::parse(v).unwrap())
+            .collect()
+    }
+
+    fn strings(versions: &[Version]) -> Vec<String> {
+        versions.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn load_skips_comments_and_drops_the_arch() {
+        let path = std::env::temp_dir().join(format!(
+            "cincinnati-graph-data-baseline-{}.txt",
+            std::process::id()
+        ));
+        std::fs::write(&path, "# expected versions\n4.5.1+amd64\n\n4.5.2 # GA\n").unwrap();
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let baseline = runtime.block_on(load(&path)).unwrap();
+        let loaded: Vec<Version> = baseline.into_iter().collect();
+        assert_eq!(strings(&loaded), vec!["4.5.1", "4.5.2"]);
+
+        std::fs::write(&path, "4.5\n").unwrap();
+        assert!(runtime.block_on(load(&path)).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn diff_lists_added_and_removed_versions() {
+        let baseline: BTreeSet<Version> = versions(&["4.5.1", "4.5.2"]).into_iter().collect();
+        let found = |found: &[&str]| -> HashSet<Version> { versions(found).into_iter().collect() };
+
+        let unchanged = diff(&baseline, &found(&["4.5.1+amd64", "4.5.2"]));
+        assert!(unchanged.added.is_empty() && unchanged.removed.is_empty());
+
+        // semver ignores build metadata when comparing, so the strings show it was dropped
+        let added = diff(&baseline, &found(&["4.5.1", "4.5.2", "4.5.3+s390x"]));
+        assert_eq!(strings(&added.added), vec!["4.5.3"]);
+        assert!(added.removed.is_empty());
+
+        let removed = diff(&baseline, &found(&["4.5.2"]));
+        assert!(removed.added.is_empty());
+        assert_eq!(strings(&removed.removed), vec!["4.5.1"]);
+    }
+}
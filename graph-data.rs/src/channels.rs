@@ -0,0 +1,575 @@
+//! Structural checks spanning several channel files.
+
+use crate::report::Problem;
+use anyhow::{Context, Result as Fallible};
+use cincinnati::plugins::internal::openshift_secondary_metadata_parser::plugin::graph_data_model::{BlockedEdge, Channel};
+use semver::Version;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Split a channel name like `stable-4.5` into its tier and minor.
+pub fn split_name(name: &str) -> Option<(&str, &str)> {
+    let mut parts = name.rsplitn(2, '-');
+    let minor = parts.next()?;
+    let tier = parts.next()?;
+    Some((tier, minor))
+}
+
+/// A channel with the path of its file.
+type ChannelFile<'a> = (&'a Path, &'a Channel);
+
+/// Group channels by minor and then by tier.
+fn by_minor(channels: &[(PathBuf, Channel)]) -> BTreeMap<&str, HashMap<&str, ChannelFile>> {
+    let mut grouped: BTreeMap<&str, HashMap<&str, ChannelFile>> = BTreeMap::new();
+    for (path, channel) in channels.iter() {
+        if let Some((tier, minor)) = split_name(&channel.name) {
+            grouped
+                .entry(minor)
+                .or_default()
+                .insert(tier, (path.as_path(), channel));
+        }
+    }
+    grouped
+}
+
+/// Report versions which were promoted to a tier without being in the previous tier of the same minor.
+///
+/// `tiers` is ordered from least to most stable; tiers without a channel for a minor are skipped.
+pub fn check_promotion_funnel(channels: &[(PathBuf, Channel)], tiers: &[String]) -> Vec<Problem> {
+    let mut errors = vec![];
+    for by_tier in by_minor(channels).values() {
+        let present: Vec<ChannelFile> = tiers
+            .iter()
+            .filter_map(|tier| by_tier.get(tier.as_str()).copied())
+            .collect();
+        for pair in present.windows(2) {
+            let ((_, feeder), (path, promoted)) = (pair[0], pair[1]);
+            let feeder_versions: HashSet<&Version> = feeder.versions.iter().collect();
+            for version in promoted.versions.iter() {
+                if !feeder_versions.contains(version) {
+                    errors.push(
+                        Problem::new(format!(
+                            "{} is in {} but missing from {}",
+                            version, promoted.name, feeder.name
+                        ))
+                        .in_file(path)
+                        .about(version),
+                    );
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Report channels without a channel of the previous tier for the same minor to be promoted from.
+///
+/// `tiers` is ordered from least to most stable; channels of the first tier need no feeder.
+pub fn check_feeders(channels: &[(PathBuf, Channel)], tiers: &[String]) -> Vec<Problem> {
+    let mut warnings = vec![];
+    for (minor, by_tier) in by_minor(channels).iter() {
+        for pair in tiers.windows(2) {
+            let (feeder, tier) = (&pair[0], &pair[1]);
+            if let Some((path, channel)) = by_tier.get(tier.as_str()) {
+                if !by_tier.contains_key(feeder.as_str()) {
+                    warnings.push(
+                        Problem::new(format!(
+                            "{} has no {}-{} to be promoted from",
+                            channel.name, feeder, minor
+                        ))
+                        .in_file(path),
+                    );
+                }
+            }
+        }
+    }
+    warnings
+}
+
+/// Report channel files whose `name` differs from their file name, like a `stable-4.5.yaml`
+/// declaring `name: stable-4.6`.
+///
+/// A symlink like `stable-4.latest.yaml` may also be named after the file it points to.
+pub fn check_names(channels: &[(PathBuf, Channel)]) -> Vec<Problem> {
+    channels
+        .iter()
+        .filter_map(|(path, channel)| {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let target = path.canonicalize().ok();
+            let target_stem = target
+                .as_ref()
+                .and_then(|target| target.file_stem())
+                .map(|stem| stem.to_string_lossy());
+            if stem == channel.name.as_str()
+                || target_stem.map_or(false, |t| t == channel.name.as_str())
+            {
+                None
+            } else {
+                Some(
+                    Problem::new(format!(
+                        "{:?}: declares name {} instead of {}",
+                        path, channel.name, stem
+                    ))
+                    .in_file(path),
+                )
+            }
+        })
+        .collect()
+}
+
+/// Report versions listed more than once within the same channel file.
+///
+/// Versions are compared including their build metadata, so `4.2.14` and `4.2.14+amd64` are distinct.
+/// Each duplicate is reported once with all of its positions in `versions`, counting from 1.
+pub fn check_duplicate_versions(channels: &[(PathBuf, Channel)]) -> Vec<Problem> {
+    let mut errors = vec![];
+    for (path, channel) in channels.iter() {
+        // In order of first appearance, indexed by `first`
+        let mut positions: Vec<(&Version, Vec<usize>)> = vec![];
+        let mut first: HashMap<String, usize> = HashMap::new();
+        for (i, version) in channel.versions.iter().enumerate() {
+            match first.get(&version.to_string()) {
+                Some(&index) => positions[index].1.push(i + 1),
+                None => {
+                    first.insert(version.to_string(), positions.len());
+                    positions.push((version, vec![i + 1]));
+                }
+            }
+        }
+        for (version, at) in positions.iter().filter(|(_, at)| at.len() > 1) {
+            let at: Vec<String> = at.iter().map(ToString::to_string).collect();
+            errors.push(
+                Problem::new(format!(
+                    "{:?}: {} lists {} more than once, at positions {}",
+                    path,
+                    channel.name,
+                    version,
+                    at.join(", ")
+                ))
+                .in_file(path)
+                .about(version),
+            );
+        }
+    }
+    errors
+}
+
+/// The first pair of versions in `channel` which is not in ascending order.
+fn first_unsorted(channel: &Channel) -> Option<(&Version, &Version)> {
+    channel
+        .versions
+        .windows(2)
+        .find(|pair| pair[0] > pair[1])
+        .map(|pair| (&pair[0], &pair[1]))
+}
+
+/// Report channels whose versions are not listed in ascending semver order.
+pub fn check_sorted(channels: &[(PathBuf, Channel)]) -> Vec<Problem> {
+    channels
+        .iter()
+        .filter_map(|(path, channel)| {
+            first_unsorted(channel).map(|(previous, next)| {
+                Problem::new(format!(
+                    "{:?}: {} lists {} before {}",
+                    path, channel.name, previous, next
+                ))
+                .in_file(path)
+                .about(next)
+            })
+        })
+        .collect()
+}
+
+/// Whether `channel` lists its versions in ascending semver order.
+pub fn is_sorted(channel: &Channel) -> bool {
+    first_unsorted(channel).is_none()
+}
+
+/// Reorder the `- <version>` entries of a channel file's text in ascending semver order.
+///
+/// Comment and blank lines move along with the entry following them, so notes about skipped
+/// versions stay next to their neighbours.
+pub fn sort_versions_text(text: &str) -> Fallible<String> {
+    let mut lines = text.lines();
+    let mut sorted: Vec<&str> = vec![];
+    loop {
+        let line = lines
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("no 'versions:' list found"))?;
+        sorted.push(line);
+        if line.trim_end() == "versions:" {
+            break;
+        }
+    }
+
+    let mut entries: Vec<(Version, Vec<&str>)> = vec![];
+    let mut pending: Vec<&str> = vec![];
+    for line in lines {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            pending.push(line);
+            continue;
+        }
+        if !trimmed.starts_with("- ") {
+            anyhow::bail!("unexpected line after the versions list: '{}'", line);
+        }
+        let entry = trimmed[2..].split('#').next().unwrap_or_default().trim();
+        let version = Version::parse(entry).context(format!("Parsing '{}'", entry))?;
+        pending.push(line);
+        entries.push((version, std::mem::take(&mut pending)));
+    }
+
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    for (_, entry_lines) in entries {
+        sorted.extend(entry_lines);
+    }
+    sorted.extend(pending);
+    Ok(sorted.join("\n") + "\n")
+}
+
+/// Whether any of `edges` blocks updating from `from` to `to`.
+///
+/// Architecture-specific blocks only count against channel entries for that same architecture.
+fn is_blocked(edges: &[(PathBuf, BlockedEdge)], from: &Version, to: &Version) -> bool {
+    let from = from.to_string();
+    edges.iter().any(|(_, edge)| {
+        edge.to == *to
+            && (edge.to.build.is_empty() || edge.to.build == to.build)
+            && edge.from.is_match(&from)
+    })
+}
+
+/// Normalize a blocked edge's `from` expression so trivially different spellings compare equal.
+///
+/// Whitespace is dropped and bracketed dots are rewritten as escaped dots.
+fn normalize_from(from: &str) -> String {
+    from.chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .replace("[.]", "\\.")
+}
+
+/// Report blocked edges which block the same `from` expression and `to` version as another edge.
+///
+/// `to` versions are compared including their build metadata, so architecture-specific edges for
+/// different architectures are not duplicates.
+pub fn check_duplicate_edges(edges: &[(PathBuf, BlockedEdge)]) -> Vec<Problem> {
+    let mut by_key: BTreeMap<(String, String), Vec<(&PathBuf, &Version)>> = BTreeMap::new();
+    for (path, edge) in edges.iter() {
+        by_key
+            .entry((normalize_from(edge.from.as_str()), edge.to.to_string()))
+            .or_default()
+            .push((path, &edge.to));
+    }
+    by_key
+        .into_iter()
+        .filter(|(_, duplicates)| duplicates.len() > 1)
+        .map(|((from, to), duplicates)| {
+            let paths: Vec<&PathBuf> = duplicates.iter().map(|(path, _)| *path).collect();
+            // Reported against the last of them, the one most likely to have been copied
+            let (path, version) = duplicates[duplicates.len() - 1];
+            Problem::new(format!(
+                "edge from '{}' to {} is blocked by each of {:?}",
+                from, to, paths
+            ))
+            .in_file(path)
+            .about(version)
+        })
+        .collect()
+}
+
+/// Report blocked edges to a version which no channel lists, so they block nothing.
+///
+/// Like `is_blocked`, an edge without an architecture matches channel entries of any architecture.
+pub fn check_membership(
+    channels: &[(PathBuf, Channel)],
+    edges: &[(PathBuf, BlockedEdge)],
+) -> Vec<Problem> {
+    edges
+        .iter()
+        .filter(|(_, edge)| {
+            !channels.iter().any(|(_, channel)| {
+                channel.versions.iter().any(|version| {
+                    edge.to == *version
+                        && (edge.to.build.is_empty() || edge.to.build == version.build)
+                })
+            })
+        })
+        .map(|(path, edge)| {
+            Problem::new(format!(
+                "{:?}: blocks edges to {}, which no channel lists",
+                path, edge.to
+            ))
+            .in_file(path)
+            .about(&edge.to)
+        })
+        .collect()
+}
+
+/// Report channel versions which have no unblocked update to a newer version in the same channel.
+///
+/// The newest version of each channel and the versions in `terminal` are expected to be dead ends.
+pub fn check_dead_ends(
+    channels: &[(PathBuf, Channel)],
+    edges: &[(PathBuf, BlockedEdge)],
+    terminal: &[Version],
+) -> Vec<Problem> {
+    let mut errors = vec![];
+    for (path, channel) in channels.iter() {
+        for from in channel.versions.iter() {
+            if terminal.contains(from) {
+                continue;
+            }
+            let mut newer = channel.versions.iter().filter(|to| *to > from).peekable();
+            if newer.peek().is_none() {
+                continue;
+            }
+            if newer.all(|to| is_blocked(edges, from, to)) {
+                errors.push(
+                    Problem::new(format!(
+                        "{:?}: every update from {} within {} is blocked",
+                        path, from, channel.name
+                    ))
+                    .in_file(path)
+                    .about(from),
+                );
+            }
+        }
+    }
+    errors
+}
+
+/// Report pre-releases still listed in a channel although their GA version is in `found_versions`.
+pub fn check_superseded_prereleases(
+    channels: &[(PathBuf, Channel)],
+    found_versions: &HashSet<Version>,
+) -> Vec<Problem> {
+    let mut warnings = vec![];
+    for (path, channel) in channels.iter() {
+        for version in channel.versions.iter().filter(|v| v.is_prerelease()) {
+            let mut ga = version.clone();
+            ga.pre.clear();
+            ga.build.clear();
+            if found_versions.contains(&ga) {
+                warnings.push(
+                    Problem::new(format!(
+                        "{:?}: {} still lists {} although {} is available",
+                        path, channel.name, version, ga
+                    ))
+                    .in_file(path)
+                    .about(version),
+                );
+            }
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(name: &str, versions: &[&str]) -> (PathBuf, Channel) {
+        let yaml = format!("name: {}\nversions: [{}]\n", name, versions.join(", "));
+        (
+            PathBuf::from(format!("channels/{}.yaml", name)),
+            serde_yaml::from_str(&yaml).unwrap(),
+        )
+    }
+
+    fn edge(to: &str, from: &str) -> (PathBuf, BlockedEdge) {
+        let yaml = format!("to: {}\nfrom: '{}'\n", to, from);
+        (
+            PathBuf::from(format!("blocked-edges/{}.yaml", to)),
+            serde_yaml::from_str(&yaml).unwrap(),
+        )
+    }
+
+    fn version(v: &str) -> Version {
+        Version::parse(v).unwrap()
+    }
+
+    fn tiers() -> Vec<String> {
+        vec![
+            "candidate".to_string(),
+            "fast".to_string(),
+            "stable".to_string(),
+        ]
+    }
+
+    #[test]
+    fn split_name_takes_the_minor_from_the_end() {
+        assert_eq!(split_name("stable-4.5"), Some(("stable", "4.5")));
+        assert_eq!(split_name("eus-stable-4.6"), Some(("eus-stable", "4.6")));
+        assert_eq!(split_name("stable"), None);
+    }
+
+    #[test]
+    fn promotion_funnel_requires_the_previous_tier() {
+        let channels = vec![
+            channel("candidate-4.5", &["4.5.1", "4.5.2"]),
+            channel("fast-4.5", &["4.5.1", "4.5.3"]),
+            channel("stable-4.6", &["4.6.1"]),
+        ];
+        assert_eq!(
+            check_promotion_funnel(&channels, &tiers()),
+            vec![
+                Problem::new("4.5.3 is in fast-4.5 but missing from candidate-4.5")
+                    .in_file("channels/fast-4.5.yaml")
+                    .about(&version("4.5.3"))
+            ]
+        );
+    }
+
+    #[test]
+    fn promotion_funnel_skips_missing_tiers() {
+        let channels = vec![
+            channel("candidate-4.5", &["4.5.1"]),
+            channel("stable-4.5", &["4.5.1"]),
+        ];
+        assert!(check_promotion_funnel(&channels, &tiers()).is_empty());
+    }
+
+    #[test]
+    fn feeders_are_required_below_the_first_tier() {
+        let channels = vec![
+            channel("candidate-4.5", &["4.5.1"]),
+            channel("stable-4.5", &["4.5.1"]),
+            channel("candidate-4.6", &["4.6.1"]),
+        ];
+        assert_eq!(
+            check_feeders(&channels, &tiers()),
+            vec![
+                Problem::new("stable-4.5 has no fast-4.5 to be promoted from")
+                    .in_file("channels/stable-4.5.yaml")
+            ]
+        );
+    }
+
+    #[test]
+    fn names_must_match_the_file() {
+        let mut renamed = channel("stable-4.6", &["4.6.1"]);
+        renamed.0 = PathBuf::from("channels/stable-4.5.yaml");
+        let channels = vec![channel("fast-4.5", &["4.5.1"]), renamed];
+        let problems = check_names(&channels);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(
+            problems[0].file,
+            Some(PathBuf::from("channels/stable-4.5.yaml"))
+        );
+        assert!(problems[0]
+            .message
+            .contains("declares name stable-4.6 instead of stable-4.5"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_may_be_named_after_their_target() {
+        let dir = std::env::temp_dir().join(format!(
+            "cincinnati-graph-data-symlinked-names-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let yaml = "name: stable-4.14\nversions: [4.14.1]\n";
+        std::fs::write(dir.join("stable-4.14.yaml"), yaml).unwrap();
+        let link = dir.join("stable-4.latest.yaml");
+        std::os::unix::fs::symlink("stable-4.14.yaml", &link).unwrap();
+        let copy = dir.join("stable-4.newest.yaml");
+        std::fs::write(&copy, yaml).unwrap();
+
+        let channel = || -> Channel { serde_yaml::from_str(yaml).unwrap() };
+        let problems = check_names(&[(link, channel()), (copy.clone(), channel())]);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].file, Some(copy));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn duplicate_versions_are_listed_with_their_positions() {
+        let channels = vec![channel("fast-4.5", &["4.5.1", "4.5.2", "4.5.1"])];
+        let problems = check_duplicate_versions(&channels);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.ends_with("at positions 1, 3"));
+        assert_eq!(problems[0].version, Some(version("4.5.1")));
+    }
+
+    #[test]
+    fn duplicate_versions_include_build_metadata() {
+        let channels = vec![channel("fast-4.5", &["4.5.1", "4.5.1+amd64"])];
+        assert!(check_duplicate_versions(&channels).is_empty());
+    }
+
+    #[test]
+    fn sorted_versions_are_ascending() {
+        let sorted = channel("fast-4.5", &["4.5.2", "4.5.10"]);
+        let unsorted = channel("stable-4.5", &["4.5.10", "4.5.2"]);
+        assert!(is_sorted(&sorted.1));
+        assert!(!is_sorted(&unsorted.1));
+        let problems = check_sorted(&[sorted, unsorted]);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0]
+            .message
+            .ends_with("stable-4.5 lists 4.5.10 before 4.5.2"));
+    }
+
+    #[test]
+    fn sorting_keeps_comments_with_the_next_entry() {
+        let text = "name: fast-4.5\nversions:\n- 4.5.10\n# 4.5.3 was pulled\n- 4.5.2\n";
+        assert_eq!(
+            sort_versions_text(text).unwrap(),
+            "name: fast-4.5\nversions:\n# 4.5.3 was pulled\n- 4.5.2\n- 4.5.10\n"
+        );
+        assert!(sort_versions_text("name: fast-4.5\n").is_err());
+        assert!(sort_versions_text("versions:\n- 4.5.1\nname: fast-4.5\n").is_err());
+    }
+
+    #[test]
+    fn duplicate_edges_ignore_spelling() {
+        let edges = vec![
+            edge("4.5.2", "4\\.5\\..*"),
+            edge("4.5.2", "4[.]5[.].*"),
+            edge("4.5.2+amd64", "4\\.5\\..*"),
+        ];
+        let problems = check_duplicate_edges(&edges);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].version, Some(version("4.5.2")));
+    }
+
+    #[test]
+    fn membership_requires_a_channel_listing_the_target() {
+        let channels = vec![channel("fast-4.5", &["4.5.1+amd64"])];
+        let edges = vec![edge("4.5.1", ".*"), edge("4.5.9", ".*")];
+        assert_eq!(
+            check_membership(&channels, &edges),
+            vec![Problem::new(
+                "\"blocked-edges/4.5.9.yaml\": blocks edges to 4.5.9, which no channel lists"
+            )
+            .in_file("blocked-edges/4.5.9.yaml")
+            .about(&version("4.5.9"))]
+        );
+    }
+
+    #[test]
+    fn dead_ends_have_every_update_blocked() {
+        let channels = vec![channel("fast-4.5", &["4.5.1", "4.5.2", "4.5.3"])];
+        let edges = vec![edge("4.5.3", "4\\.5\\.1")];
+        assert!(check_dead_ends(&channels, &edges, &[]).is_empty());
+
+        let edges = vec![edge("4.5.2", "4\\.5\\.1"), edge("4.5.3", "4\\.5\\.1")];
+        let problems = check_dead_ends(&channels, &edges, &[]);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].version, Some(version("4.5.1")));
+        assert!(check_dead_ends(&channels, &edges, &[version("4.5.1")]).is_empty());
+    }
+
+    #[test]
+    fn superseded_prereleases_need_their_ga_version() {
+        let channels = vec![channel("candidate-4.6", &["4.6.0-rc.1", "4.6.1-rc.0"])];
+        let found: HashSet<Version> = vec![version("4.6.0")].into_iter().collect();
+        let problems = check_superseded_prereleases(&channels, &found);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].version, Some(version("4.6.0-rc.1")));
+    }
+}
@@ -1,42 +1,381 @@
 use cincinnati::plugins::internal::release_scrape_dockerv2::plugin;
 use cincinnati::plugins::internal::release_scrape_dockerv2::registry;
 
+use crate::checks::Check;
+use crate::cli::Options;
+use crate::docker_config;
+use crate::http;
+use crate::report::{Counter, Problem, Report, Severity};
 use anyhow::Context;
 use anyhow::Result as Fallible;
 use semver::Version;
 use std::collections::HashSet;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tracing::info;
 
-pub async fn run(found_versions: &HashSet<Version>) -> Fallible<()> {
-    let settings = plugin::ReleaseScrapeDockerv2Settings::default();
+const SUITE: &str = "check_releases";
+
+/// Maximum number of concurrency values a benchmark sweep may scrape with.
+const MAX_BENCH_RUNS: usize = 5;
+
+/// The longest a rate limited scrape waits before it is retried, whatever the registry asks for.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10 * 60);
+
+/// How often to log that a scrape is still in flight.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Await `future`, logging every `PROGRESS_INTERVAL` that `what` is still in flight.
+///
+/// cincinnati doesn't report progress while fetching release metadata, so elapsed time is all
+/// there is to show.
+async fn with_progress<T>(what: &str, future: impl Future<Output = T>) -> T {
+    let start = Instant::now();
+    tokio::pin!(future);
+    loop {
+        tokio::select! {
+            output = &mut future => return output,
+            _ = tokio::time::delay_for(PROGRESS_INTERVAL) => {
+                info!("Still {} after {}s", what, start.elapsed().as_secs());
+            }
+        }
+    }
+}
+
+async fn scrape(settings: &plugin::ReleaseScrapeDockerv2Settings) -> Fallible<HashSet<Version>> {
     let cache = registry::cache::new();
     let registry = registry::Registry::try_from_str(&settings.registry)
         .context(format!("Parsing {} as Registry", &settings.registry))?;
 
-    println!("Scraping Quay registry");
-    let released_versions: HashSet<Version> = registry::fetch_releases(
-        &registry,
-        &settings.repository,
-        settings.username.as_ref().map(String::as_ref),
-        settings.password.as_ref().map(String::as_ref),
-        cache,
-        &settings.manifestref_key,
-        settings.fetch_concurrency,
+    let credentials = match (&settings.username, &settings.password) {
+        (None, None) => docker_config::lookup(&settings.registry)?,
+        _ => None,
+    };
+    let (username, password) = match &credentials {
+        Some(credentials) => (
+            Some(credentials.username.as_str()),
+            Some(credentials.password.as_str()),
+        ),
+        None => (
+            settings.username.as_ref().map(String::as_ref),
+            settings.password.as_ref().map(String::as_ref),
+        ),
+    };
+
+    let what = format!("scraping {}/{}", settings.registry, settings.repository);
+    let released_versions: HashSet<Version> = with_progress(
+        &what,
+        registry::fetch_releases(
+            &registry,
+            &settings.repository,
+            username,
+            password,
+            cache,
+            &settings.manifestref_key,
+            settings.fetch_concurrency,
+        ),
     )
     .await
     .context("failed to fetch all release metadata")?
     .into_iter()
     .map(|r| r.metadata.version)
     .collect();
+    info!("Scraped {} releases", released_versions.len());
+    Ok(released_versions)
+}
+
+/// cincinnati's scrape settings, with the registry and repository of `opts` if they are given.
+fn settings(opts: &Options) -> plugin::ReleaseScrapeDockerv2Settings {
+    let mut settings = plugin::ReleaseScrapeDockerv2Settings::default();
+    if let Some(registry) = &opts.registry {
+        settings.registry = registry.clone();
+    }
+    if let Some(repository) = &opts.repository {
+        settings.repository = repository.clone();
+    }
+    settings
+}
+
+/// Whether `error` was caused by the registry responding with HTTP 429.
+fn is_rate_limited(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .find_map(reqwest::Error::status)
+        == Some(reqwest::StatusCode::TOO_MANY_REQUESTS)
+}
+
+/// The registry is rate limiting us, asking to wait `retry_after` if it said how long.
+#[derive(Debug, PartialEq)]
+struct RateLimited {
+    retry_after: Option<Duration>,
+}
+
+/// The API root of the registry of `settings`, over HTTPS unless it names a scheme.
+fn api_root(settings: &plugin::ReleaseScrapeDockerv2Settings) -> String {
+    let registry = settings.registry.trim_end_matches('/');
+    if registry.contains("://") {
+        format!("{}/v2/", registry)
+    } else {
+        format!("https://{}/v2/", registry)
+    }
+}
+
+/// Ask the registry at `url` whether it is rate limiting us.
+///
+/// cincinnati doesn't pass on the response of a failed scrape, so its `Retry-After` header is read
+/// from this request instead. Only a number of seconds is understood, not an HTTP date.
+async fn probe(client: &reqwest::Client, url: &str) -> Option<RateLimited> {
+    let response = client.get(url).send().await.ok()?;
+    if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .map(Duration::from_secs);
+    Some(RateLimited { retry_after })
+}
+
+/// Scrape the registry of `settings`, retrying while it is rate limited.
+async fn scrape_with_retries(
+    settings: &plugin::ReleaseScrapeDockerv2Settings,
+    opts: &Options,
+    report: &Report,
+) -> Fallible<HashSet<Version>> {
+    let client = http::client(opts)?;
+    with_retries(opts, report, &client, &api_root(settings), || {
+        scrape(settings)
+    })
+    .await
+}
+
+/// Run `scrape` until it succeeds, retrying up to `--rate-limit-retries` times while the registry
+/// at `url` is rate limiting us.
+///
+/// Each retry waits as long as the registry's `Retry-After` asks, up to `MAX_RETRY_DELAY`.
+/// Without one it waits `--rate-limit-delay`, doubled on every retry.
+async fn with_retries<F, Fut>(
+    opts: &Options,
+    report: &Report,
+    client: &reqwest::Client,
+    url: &str,
+    mut scrape: F,
+) -> Fallible<HashSet<Version>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Fallible<HashSet<Version>>>,
+{
+    let mut delay = Duration::from_secs(opts.rate_limit_delay);
+    let mut attempt = 0;
+    loop {
+        let e = match scrape().await {
+            Ok(released_versions) => return Ok(released_versions),
+            Err(e) => e,
+        };
+        report.count(Counter::RegistryErrors, 1);
+        if attempt == opts.rate_limit_retries {
+            return Err(e);
+        }
+        let retry_after = match probe(client, url).await {
+            Some(limited) => limited.retry_after,
+            None if is_rate_limited(&e) => None,
+            None => return Err(e),
+        };
+        attempt += 1;
+        let wait = retry_after.unwrap_or(delay).min(MAX_RETRY_DELAY);
+        tracing::warn!(
+            "Registry is rate limiting, retrying in {}s ({}/{})",
+            wait.as_secs(),
+            attempt,
+            opts.rate_limit_retries
+        );
+        tokio::time::delay_for(wait).await;
+        delay *= 2;
+    }
+}
 
-    println!("Verifying all releases are uploaded");
-    let missing_versions: HashSet<&Version> =
+pub async fn run(
+    opts: &Options,
+    found_versions: &HashSet<Version>,
+    report: &Report,
+) -> Fallible<()> {
+    let settings = settings(opts);
+    info!("Scraping {}/{}", settings.registry, settings.repository);
+    let start = Instant::now();
+    let result = scrape_with_retries(&settings, opts, report).await;
+    report.time_check(SUITE, "scrape", start.elapsed());
+    let released_versions = report.record_result(SUITE, "scrape", result)?;
+    report.count(Counter::ReleasesScraped, released_versions.len());
+
+    info!("Verifying all releases are uploaded");
+    let start = Instant::now();
+    let mut sorted_versions: Vec<&Version> = found_versions.iter().collect();
+    sorted_versions.sort();
+    for version in sorted_versions {
+        let span = tracing::debug_span!("version", %version);
+        let _entered = span.enter();
+        let failure = if released_versions.contains(version) {
+            None
+        } else {
+            let message = format!("{} is missing in scraped images", version);
+            report.finding(
+                SUITE,
+                Check::RegistryPresence.name(),
+                Severity::Error,
+                Problem::new(message.as_str()).about(version),
+            );
+            Some(message)
+        };
+        report.record(SUITE, version.to_string(), failure);
+    }
+
+    let mut missing_versions: Vec<&Version> =
         found_versions.difference(&released_versions).collect();
+    missing_versions.sort();
+    report.count(Counter::VersionsMissing, missing_versions.len());
+    report.time_check(SUITE, Check::RegistryPresence.name(), start.elapsed());
     if missing_versions.is_empty() {
         Ok(())
     } else {
+        let missing_versions: Vec<String> =
+            missing_versions.iter().map(ToString::to_string).collect();
         Err(anyhow::anyhow!(
-            "Missing the following versions in scraped images: {:?}",
-            missing_versions
+            "Missing the following versions in scraped images: {}",
+            missing_versions.join(", ")
         ))
     }
 }
+
+/// Print whether `version` has been published to the release registry.
+pub async fn explain(opts: &Options, version: &Version) -> Fallible<()> {
+    let settings = settings(opts);
+    info!("Scraping {}/{}", settings.registry, settings.repository);
+    let released_versions = scrape_with_retries(&settings, opts, &Report::default()).await?;
+    let published: Vec<&Version> = released_versions
+        .iter()
+        .filter(|released| *released == version)
+        .collect();
+    if published.is_empty() {
+        println!(
+            "  missing from the {} scraped releases",
+            released_versions.len()
+        );
+    }
+    for released in published {
+        println!("  published as {}", released);
+    }
+    Ok(())
+}
+
+/// Scrape the registry once per value in `concurrencies` and print how long each scrape took.
+pub async fn bench(opts: &Options, concurrencies: &[usize]) -> Fallible<()> {
+    if concurrencies.len() > MAX_BENCH_RUNS {
+        anyhow::bail!(
+            "Refusing to scrape the registry more than {} times in one sweep",
+            MAX_BENCH_RUNS
+        );
+    }
+
+    let mut results = vec![];
+    for &concurrency in concurrencies.iter() {
+        let mut settings = settings(opts);
+        settings.fetch_concurrency = concurrency;
+
+        info!(
+            "Scraping {}/{} with concurrency {}",
+            settings.registry, settings.repository, concurrency
+        );
+        let start = Instant::now();
+        let released_versions = scrape(&settings).await?;
+        results.push((concurrency, start.elapsed(), released_versions.len()));
+    }
+
+    println!(
+        "{:>12} {:>12} {:>12}",
+        "concurrency", "duration", "releases"
+    );
+    for (concurrency, duration, releases) in results {
+        println!(
+            "{:>12} {:>11.1}s {:>12}",
+            concurrency,
+            duration.as_secs_f64(),
+            releases
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::tests::{serve, OK};
+
+    /// A 429 asking to retry after a second.
+    const TOO_MANY_REQUESTS: &str = "HTTP/1.1 429 Too Many Requests\r\nretry-after: 1\r\n\
+                                     content-length: 0\r\nconnection: close\r\n\r\n";
+
+    #[test]
+    fn rate_limited_scrapes_wait_as_long_as_the_registry_asks() {
+        // The scrape is refused, the probe explains why, and the retried scrape goes through
+        let (url, heads) = serve(vec![TOO_MANY_REQUESTS, TOO_MANY_REQUESTS, OK]);
+        let url = format!("{}/v2/", url);
+        let opts = Options {
+            rate_limit_delay: 60 * 60,
+            ..Options::default()
+        };
+        let report = Report::default();
+        let client = &http::client(&opts).unwrap();
+        let url = url.as_str();
+        let started = Instant::now();
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let released_versions = runtime
+            .block_on(with_retries(
+                &opts,
+                &report,
+                client,
+                url,
+                move || async move {
+                    client.get(url).send().await?.error_for_status()?;
+                    let released_versions: HashSet<Version> =
+                        vec![Version::parse("4.5.1").unwrap()].into_iter().collect();
+                    Ok::<_, anyhow::Error>(released_versions)
+                },
+            ))
+            .unwrap();
+
+        assert_eq!(released_versions.len(), 1);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert!(started.elapsed() < Duration::from_secs(60));
+        assert_eq!(heads.try_iter().count(), 3);
+        assert_eq!(report.summary().counters[&Counter::RegistryErrors], 1);
+    }
+
+    #[test]
+    fn other_failures_are_not_retried() {
+        let (url, _) = serve(vec![OK]);
+        let opts = Options::default();
+        let report = Report::default();
+        let client = http::client(&opts).unwrap();
+        let mut attempts = 0;
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let result = runtime.block_on(with_retries(&opts, &report, &client, &url, || {
+            attempts += 1;
+            async { Err::<HashSet<Version>, _>(anyhow::anyhow!("manifest unknown")) }
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn registries_are_reached_over_https_by_default() {
+        let mut settings = plugin::ReleaseScrapeDockerv2Settings::default();
+        settings.registry = "quay.io".to_string();
+        assert_eq!(api_root(&settings), "https://quay.io/v2/");
+        settings.registry = "http://localhost:5000/".to_string();
+        assert_eq!(api_root(&settings), "http://localhost:5000/v2/");
+    }
+}
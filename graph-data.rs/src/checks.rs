@@ -0,0 +1,187 @@
+//! The named checks which can be selected with `--checks` or deselected with `--skip-checks`.
+
+use anyhow::Result as Fallible;
+
+/// A check of the graph data or the registry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Check {
+    YamlSyntax,
+    BuildMetadata,
+    VersionBounds,
+    BlockedEdgeArch,
+    DuplicateEdges,
+    ChannelMembership,
+    ChannelNames,
+    DuplicateVersions,
+    SortedVersions,
+    FeederChannels,
+    PromotionFunnel,
+    DeadEnds,
+    SupersededPrereleases,
+    Baseline,
+    MonotonicChannels,
+    RegistryPresence,
+}
+
+impl Check {
+    fn descriptor(self) -> &'static Descriptor {
+        CHECKS
+            .iter()
+            .find(|descriptor| descriptor.check == self)
+            .expect("every check has a descriptor")
+    }
+
+    /// The name selecting the check on the command line and identifying it in reports.
+    pub fn name(self) -> &'static str {
+        self.descriptor().name
+    }
+
+    /// What the check verifies, completing "Verifying ...".
+    pub fn description(self) -> &'static str {
+        self.descriptor().description
+    }
+}
+
+/// How a check is named and described.
+#[derive(Debug)]
+pub struct Descriptor {
+    pub check: Check,
+    pub name: &'static str,
+    /// What the check verifies, completing "Verifying ...".
+    pub description: &'static str,
+}
+
+/// Every check, in the order they run.
+///
+/// `blocked-edge-arch`, `sorted-versions`, `baseline` and `monotonic-channels` additionally need
+/// their own flags to run. Deselecting `yaml-syntax` still parses the files the other checks need,
+/// only without reporting the ones which are invalid.
+pub const CHECKS: &[Descriptor] = &[
+    Descriptor {
+        check: Check::YamlSyntax,
+        name: "yaml-syntax",
+        description: "the graph data files are valid",
+    },
+    Descriptor {
+        check: Check::BuildMetadata,
+        name: "build-metadata",
+        description: "build metadata only names architectures",
+    },
+    Descriptor {
+        check: Check::VersionBounds,
+        name: "version-bounds",
+        description: "version components are plausible",
+    },
+    Descriptor {
+        check: Check::BlockedEdgeArch,
+        name: "blocked-edge-arch",
+        description: "blocked edge versions are canonical",
+    },
+    Descriptor {
+        check: Check::DuplicateEdges,
+        name: "duplicate-edges",
+        description: "blocked edges don't duplicate each other",
+    },
+    Descriptor {
+        check: Check::ChannelMembership,
+        name: "channel-membership",
+        description: "blocked edges lead to versions in a channel",
+    },
+    Descriptor {
+        check: Check::ChannelNames,
+        name: "channel-names",
+        description: "channel names match their file names",
+    },
+    Descriptor {
+        check: Check::DuplicateVersions,
+        name: "duplicate-versions",
+        description: "channels don't list a version twice",
+    },
+    Descriptor {
+        check: Check::SortedVersions,
+        name: "sorted-versions",
+        description: "channel versions are sorted",
+    },
+    Descriptor {
+        check: Check::FeederChannels,
+        name: "feeder-channels",
+        description: "channels have a feeder tier",
+    },
+    Descriptor {
+        check: Check::PromotionFunnel,
+        name: "promotion-funnel",
+        description: "channel tiers form a promotion funnel",
+    },
+    Descriptor {
+        check: Check::DeadEnds,
+        name: "dead-ends",
+        description: "blocked edges don't strand versions",
+    },
+    Descriptor {
+        check: Check::SupersededPrereleases,
+        name: "superseded-prereleases",
+        description: "channels don't keep superseded pre-releases",
+    },
+    Descriptor {
+        check: Check::Baseline,
+        name: "baseline",
+        description: "no versions were removed since the baseline",
+    },
+    Descriptor {
+        check: Check::MonotonicChannels,
+        name: "monotonic-channels",
+        description: "the most stable channels only grew",
+    },
+    Descriptor {
+        check: Check::RegistryPresence,
+        name: "registry-presence",
+        description: "all releases are uploaded",
+    },
+];
+
+/// Look up the check called `name`.
+pub fn find(name: &str) -> Option<&'static Descriptor> {
+    CHECKS.iter().find(|check| check.name == name)
+}
+
+/// Parse a check name given on the command line, rejecting unknown ones.
+pub fn parse_name(name: &str) -> Fallible<Check> {
+    match find(name) {
+        Some(descriptor) => Ok(descriptor.check),
+        None => {
+            let names: Vec<&str> = CHECKS.iter().map(|check| check.name).collect();
+            Err(anyhow::anyhow!(
+                "unknown check '{}', expected one of: {}",
+                name,
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_check_is_registered_once() {
+        for descriptor in CHECKS.iter() {
+            assert_eq!(descriptor.check.name(), descriptor.name);
+            assert_eq!(parse_name(descriptor.name).unwrap(), descriptor.check);
+            let same = CHECKS
+                .iter()
+                .filter(|other| other.check == descriptor.check || other.name == descriptor.name)
+                .count();
+            assert_eq!(same, 1, "{} is registered more than once", descriptor.name);
+        }
+        assert_eq!(Check::YamlSyntax.name(), "yaml-syntax");
+        assert_eq!(Check::ChannelMembership.name(), "channel-membership");
+    }
+
+    #[test]
+    fn unknown_names_list_the_known_ones() {
+        let e = parse_name("dead-end").unwrap_err().to_string();
+        assert!(e.starts_with("unknown check 'dead-end', expected one of: yaml-syntax, "));
+        assert!(e.contains("dead-ends"));
+    }
+}
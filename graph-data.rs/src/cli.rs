@@ -0,0 +1,606 @@
+use crate::checks::{self, Check};
+use crate::config;
+use crate::verify_yaml::{ArchConvention, Minor, Shard, Target, VersionFilter};
+use anyhow::Result as Fallible;
+use semver::Version;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    about = "Validate the Cincinnati graph data",
+    after_help = "EXIT CODES:\n    0    all checks passed\n    1    another failure\n    2    the graph data is invalid\n    3    releases are missing from the registry\n    4    the --deadline passed\n    5    the registry couldn't be scraped\n    130  interrupted"
+)]
+pub struct Options {
+    /// The phases to run, all of them if omitted
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+
+    /// Root of the graph data checkout
+    #[structopt(
+        long = "data-dir",
+        env = "GRAPH_DATA_DIR",
+        default_value = "..",
+        parse(from_os_str),
+        global = true
+    )]
+    pub data_dir: PathBuf,
+
+    /// Log more details, `-v` for every parsed file and `-vv` for everything
+    #[structopt(short = "v", long = "verbose", parse(from_occurrences), global = true)]
+    pub verbose: u8,
+
+    /// Only log warnings and errors
+    #[structopt(short = "q", long = "quiet", conflicts_with = "verbose", global = true)]
+    pub quiet: bool,
+
+    /// Log as `human` readable lines or as `json` objects
+    #[structopt(long = "log-format", default_value = "human", global = true)]
+    pub log_format: LogFormat,
+
+    /// Architectures which may appear as version build metadata
+    #[structopt(
+        long = "arches",
+        default_value = "amd64,arm64,multi,ppc64le,s390x",
+        use_delimiter = true,
+        global = true
+    )]
+    pub arches: Vec<String>,
+
+    /// Smallest plausible major version
+    #[structopt(long = "min-major", default_value = "3", global = true)]
+    pub min_major: u64,
+
+    /// Largest plausible major version
+    #[structopt(long = "max-major", default_value = "9", global = true)]
+    pub max_major: u64,
+
+    /// Largest plausible minor version
+    #[structopt(long = "max-minor", default_value = "99", global = true)]
+    pub max_minor: u64,
+
+    /// Largest plausible patch version
+    #[structopt(long = "max-patch", default_value = "999", global = true)]
+    pub max_patch: u64,
+
+    /// Require blocked edge versions to either `omit` or `require` an architecture suffix
+    #[structopt(long = "blocked-edge-arch", global = true)]
+    pub blocked_edge_arch: Option<ArchConvention>,
+
+    /// Same as `--output junit=PATH`
+    #[structopt(long = "junit-out", parse(from_os_str), global = true)]
+    pub junit_out: Option<PathBuf>,
+
+    /// Write a report in FORMAT to PATH, or to stdout without `=PATH`; repeatable. FORMAT is
+    /// `json`, `junit`, `sarif`, `tap`, `gha` or `markdown`
+    #[structopt(
+        long = "output",
+        value_name = "FORMAT[=PATH]",
+        number_of_values = 1,
+        global = true
+    )]
+    pub outputs: Vec<Output>,
+
+    /// Post a summary of the run as a comment on this pull request, updating the comment of an
+    /// earlier run
+    #[structopt(long = "pr", global = true)]
+    pub pr: Option<u64>,
+
+    /// The repository of `--pr`, like `openshift/cincinnati-graph-data`
+    #[structopt(long = "github-repo", env = "GITHUB_REPOSITORY", global = true)]
+    pub github_repo: Option<String>,
+
+    /// The token to comment on `--pr` with
+    #[structopt(
+        long = "github-token",
+        env = "GITHUB_TOKEN",
+        hide_env_values = true,
+        global = true
+    )]
+    pub github_token: Option<String>,
+
+    /// Post the failed checks and versions to this webhook, e.g. a Slack incoming webhook, when
+    /// the run fails
+    #[structopt(long = "notify-url", global = true)]
+    pub notify_url: Option<String>,
+
+    /// Write a normalized dump of the parsed channels and blocked edges to this file, `-` for stdout
+    #[structopt(long = "dump-graph", parse(from_os_str), global = true)]
+    pub dump_graph: Option<PathBuf>,
+
+    /// Write run metrics to this file in the Prometheus text format, e.g. for the node exporter's
+    /// textfile collector
+    #[structopt(long = "metrics-out", parse(from_os_str), global = true)]
+    pub metrics_out: Option<PathBuf>,
+
+    /// Push run metrics to the Prometheus Pushgateway at this URL
+    #[structopt(long = "metrics-push-url", global = true)]
+    pub metrics_push_url: Option<String>,
+
+    /// Only check versions of this MAJOR.MINOR and newer against the registry; all files are still parsed
+    #[structopt(long = "since", global = true)]
+    pub since: Option<Minor>,
+
+    /// Only parse these channels, e.g. `stable-4.5`, and only check their versions against the registry
+    #[structopt(long = "channel", use_delimiter = true, global = true)]
+    pub channels: Vec<String>,
+
+    /// Only parse the staged channel files, the other tiers of their minors and all blocked edges,
+    /// and only check the versions in staged files; runs `verify-yaml` unless a command is given
+    #[structopt(long = "staged", global = true)]
+    pub staged: bool,
+
+    /// Only check versions which are new since this git revision like `origin/master` against the
+    /// registry; all files are still parsed and checked
+    #[structopt(long = "base-ref", global = true)]
+    pub base_ref: Option<String>,
+
+    /// Check all versions against the registry, even with `--base-ref`
+    #[structopt(long = "all", global = true)]
+    pub all: bool,
+
+    /// Only check versions matching this glob like `4.5.*` or semver range like `>=4.5, <4.7`
+    /// against the registry; all files are still parsed
+    #[structopt(long = "filter-version", global = true)]
+    pub filter_version: Option<VersionFilter>,
+
+    /// Only check this part of the versions like `2/8` against the registry, so CI jobs can split
+    /// the registry work; all files are still parsed
+    #[structopt(long = "shard", global = true)]
+    pub shard: Option<Shard>,
+
+    /// Stop after this long like `90s`, `15m` or `1h`, reporting the phases which didn't finish as
+    /// not run; pushing metrics and posting comments or notifications must finish in time too
+    #[structopt(long = "deadline", parse(try_from_str = parse_duration), global = true)]
+    pub deadline: Option<Duration>,
+
+    /// How long a request posting the results may take, like `30s` or `2m`
+    #[structopt(
+        long = "http-timeout",
+        default_value = "30s",
+        parse(try_from_str = parse_duration),
+        global = true
+    )]
+    pub http_timeout: Duration,
+
+    /// PEM file of additional certificate authorities to trust when posting the results
+    #[structopt(
+        long = "ca-bundle",
+        env = "GRAPH_DATA_CA_BUNDLE",
+        parse(from_os_str),
+        global = true
+    )]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// Only print the versions which would be checked against the registry
+    #[structopt(long = "list-versions", global = true)]
+    pub list_versions: bool,
+
+    /// How often to retry the registry scrape while it is rate limited
+    #[structopt(long = "rate-limit-retries", default_value = "3", global = true)]
+    pub rate_limit_retries: u32,
+
+    /// Seconds to wait before the first retry of a rate limited scrape, doubled on every retry,
+    /// unless the registry sends a `Retry-After`
+    #[structopt(long = "rate-limit-delay", default_value = "30", global = true)]
+    pub rate_limit_delay: u64,
+
+    /// Scrape this registry like `quay.io` instead of cincinnati's default
+    #[structopt(long = "registry", global = true)]
+    pub registry: Option<String>,
+
+    /// Scrape this repository like `openshift-release-dev/ocp-release` instead of cincinnati's
+    /// default
+    #[structopt(long = "repository", global = true)]
+    pub repository: Option<String>,
+
+    /// Only describe where this version is mentioned and whether it is in the registry
+    #[structopt(long = "explain", global = true)]
+    pub explain: Option<Version>,
+
+    /// Skip every check which needs the network, like the registry scrape
+    #[structopt(long = "offline", global = true)]
+    pub offline: bool,
+
+    /// Re-run the YAML checks whenever a file below the data directory changes
+    #[structopt(long = "watch", global = true)]
+    pub watch: bool,
+
+    /// Re-run all checks instead of only the YAML checks in watch mode
+    #[structopt(long = "watch-all", requires = "watch", global = true)]
+    pub watch_all: bool,
+
+    /// Only benchmark registry scraping with each of the given fetch concurrencies
+    #[structopt(
+        long = "bench-concurrency",
+        use_delimiter = true,
+        parse(try_from_str = parse_concurrency),
+        global = true
+    )]
+    pub bench_concurrency: Vec<usize>,
+
+    /// Only run these checks
+    #[structopt(
+        long = "checks",
+        use_delimiter = true,
+        parse(try_from_str = checks::parse_name),
+        global = true
+    )]
+    pub checks: Vec<Check>,
+
+    /// Don't run these checks
+    #[structopt(
+        long = "skip-checks",
+        use_delimiter = true,
+        parse(try_from_str = checks::parse_name),
+        global = true
+    )]
+    pub skip_checks: Vec<Check>,
+
+    /// Stop at the first failing check instead of running all checks and reporting every failure
+    #[structopt(long = "fail-fast", global = true)]
+    pub fail_fast: bool,
+
+    /// Exit with a failure if any check reported a warning, after running all checks
+    #[structopt(long = "warnings-as-errors", global = true)]
+    pub warnings_as_errors: bool,
+
+    /// Treat warnings as failures of the check reporting them
+    #[structopt(long = "strict", global = true)]
+    pub strict: bool,
+
+    /// Require channel versions to be listed in ascending semver order
+    #[structopt(long = "check-sorted", global = true)]
+    pub check_sorted: bool,
+
+    /// Rewrite channel files so their versions are in ascending semver order
+    #[structopt(long = "fix-sorted", global = true)]
+    pub fix_sorted: bool,
+
+    /// Channel tiers in promotion order, from least to most stable
+    #[structopt(
+        long = "tiers",
+        default_value = "candidate,fast,stable",
+        use_delimiter = true,
+        global = true
+    )]
+    pub tiers: Vec<String>,
+
+    /// Version which is intentionally left without updates, e.g. because it is end of life
+    #[structopt(long = "terminal-version", number_of_values = 1, global = true)]
+    pub terminal_versions: Vec<Version>,
+
+    /// Compare the found versions against this file listing one expected version per line
+    #[structopt(long = "baseline", parse(from_os_str), global = true)]
+    pub baseline: Option<PathBuf>,
+
+    /// Also list versions which are not in the baseline
+    #[structopt(long = "baseline-additions", global = true)]
+    pub baseline_additions: bool,
+
+    /// Fail if versions in the baseline are no longer in the graph data
+    #[structopt(long = "no-removals", global = true)]
+    pub no_removals: bool,
+
+    /// Fail if a channel of the most stable tier dropped a version since this git revision,
+    /// unless a blocked edge to that version was added since
+    #[structopt(long = "monotonic-since", global = true)]
+    pub monotonic_since: Option<String>,
+
+    /// Fail on keys the channel and blocked edge models don't know, like `verions:`, instead of
+    /// ignoring them; set `deny-unknown-fields: false` in the config file during schema migrations
+    #[structopt(long = "deny-unknown-fields", global = true)]
+    pub deny_unknown_fields: bool,
+
+    /// Additional DIR=MODEL directory to validate, MODEL being `channel` or `blocked-edge`
+    #[structopt(long = "validate-dir", number_of_values = 1, global = true)]
+    pub extra_targets: Vec<Target>,
+}
+
+impl Options {
+    /// Parse the command line, taking options it doesn't set from the configuration file
+    /// `.graph-data-checks.yaml` at the root of the data directory.
+    ///
+    /// Flags win over environment variables, which win over the configuration file, which wins
+    /// over the built-in defaults.
+    pub fn load() -> Fallible<Self> {
+        let matches = Self::clap().get_matches();
+        let mut opts = Self::from_clap(&matches);
+        config::apply(&mut opts, &matches)?;
+        if let Some(path) = opts.junit_out.take() {
+            opts.outputs.push(Output {
+                format: OutputFormat::Junit,
+                path: Some(path),
+            });
+        }
+        Ok(opts)
+    }
+
+    /// Whether `check` was selected by `--checks` and not deselected by `--skip-checks`.
+    pub fn is_selected(&self, check: Check) -> bool {
+        (self.checks.is_empty() || self.checks.contains(&check))
+            && !self.skip_checks.contains(&check)
+    }
+
+    /// The phases to run.
+    pub fn command(&self) -> Command {
+        match self.command {
+            Some(command) => command,
+            None if self.staged => Command::VerifyYaml,
+            None => Command::All,
+        }
+    }
+}
+
+/// The options of a run without any flags, environment variables or configuration file, for
+/// library users to override single fields of.
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            command: None,
+            data_dir: PathBuf::from(".."),
+            verbose: 0,
+            quiet: false,
+            log_format: LogFormat::Human,
+            arches: ["amd64", "arm64", "multi", "ppc64le", "s390x"]
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            min_major: 3,
+            max_major: 9,
+            max_minor: 99,
+            max_patch: 999,
+            blocked_edge_arch: None,
+            junit_out: None,
+            outputs: vec![],
+            pr: None,
+            github_repo: None,
+            github_token: None,
+            notify_url: None,
+            dump_graph: None,
+            metrics_out: None,
+            metrics_push_url: None,
+            since: None,
+            channels: vec![],
+            staged: false,
+            base_ref: None,
+            all: false,
+            filter_version: None,
+            shard: None,
+            deadline: None,
+            http_timeout: Duration::from_secs(30),
+            ca_bundle: None,
+            list_versions: false,
+            rate_limit_retries: 3,
+            rate_limit_delay: 30,
+            registry: None,
+            repository: None,
+            explain: None,
+            offline: false,
+            watch: false,
+            watch_all: false,
+            bench_concurrency: vec![],
+            checks: vec![],
+            skip_checks: vec![],
+            fail_fast: false,
+            warnings_as_errors: false,
+            strict: false,
+            check_sorted: false,
+            fix_sorted: false,
+            tiers: ["candidate", "fast", "stable"]
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            terminal_versions: vec![],
+            baseline: None,
+            baseline_additions: false,
+            no_removals: false,
+            monotonic_since: None,
+            deny_unknown_fields: false,
+            extra_targets: vec![],
+        }
+    }
+}
+
+/// Parse a fetch concurrency, which must be at least 1.
+fn parse_concurrency(s: &str) -> Fallible<usize> {
+    match s.parse() {
+        Ok(0) => anyhow::bail!("concurrency must be at least 1"),
+        Ok(concurrency) => Ok(concurrency),
+        Err(_) => anyhow::bail!("expected a concurrency like '16', got '{}'", s),
+    }
+}
+
+/// Parse a number of seconds, minutes or hours like `90s`, `15m` or `1h`; a bare number is seconds.
+pub(crate) fn parse_duration(s: &str) -> Fallible<Duration> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("expected a duration like '15m', got '{}'", s))?;
+    let factor = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        _ => anyhow::bail!(
+            "unknown unit '{}' in '{}', expected 's', 'm' or 'h'",
+            unit,
+            s
+        ),
+    };
+    let seconds = number
+        .checked_mul(factor)
+        .ok_or_else(|| anyhow::anyhow!("duration '{}' is too long", s))?;
+    Ok(Duration::from_secs(seconds))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, StructOpt)]
+pub enum Command {
+    /// Only parse and validate the channel and blocked edge files
+    VerifyYaml,
+    /// Only check that the versions mentioned in the graph data are in the release registry
+    CheckReleases,
+    /// Validate the graph data, then check the registry
+    All,
+    /// Print a CSV with a row per version and architecture and a column per channel
+    Matrix,
+}
+
+/// A report requested with `--output`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Output {
+    pub format: OutputFormat,
+    /// Where to write the report, `None` for stdout.
+    pub path: Option<PathBuf>,
+}
+
+impl FromStr for Output {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Fallible<Self> {
+        let mut parts = s.splitn(2, '=');
+        let format = parts.next().unwrap_or_default().parse()?;
+        let path = match parts.next() {
+            None | Some("-") => None,
+            Some(path) => Some(PathBuf::from(path)),
+        };
+        Ok(Output { format, path })
+    }
+}
+
+/// The format of a report requested with `--output`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Json,
+    /// Every check and version as a test case, for CI systems rendering JUnit XML.
+    Junit,
+    /// Findings with their file and line, for GitHub code scanning.
+    Sarif,
+    /// One ok or not ok line per check, in the Test Anything Protocol.
+    Tap,
+    /// GitHub Actions workflow commands annotating the pull request with each finding.
+    GithubActions,
+    /// Tables of the checks and their findings, for CI artifacts and incident docs.
+    Markdown,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Fallible<Self> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "junit" => Ok(OutputFormat::Junit),
+            "sarif" => Ok(OutputFormat::Sarif),
+            "tap" => Ok(OutputFormat::Tap),
+            "gha" => Ok(OutputFormat::GithubActions),
+            "markdown" => Ok(OutputFormat::Markdown),
+            _ => Err(anyhow::anyhow!(
+                "unknown output format '{}', expected one of 'json', 'junit', 'sarif', 'tap', \
+                 'gha' or 'markdown'",
+                s
+            )),
+        }
+    }
+}
+
+/// The format of log lines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    Human,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Fallible<Self> {
+        match s {
+            "human" => Ok(LogFormat::Human),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(anyhow::anyhow!(
+                "unknown log format '{}', expected 'human' or 'json'",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_matches_command_line_defaults() {
+        for name in [
+            "GRAPH_DATA_DIR",
+            "GRAPH_DATA_CA_BUNDLE",
+            "GITHUB_REPOSITORY",
+            "GITHUB_TOKEN",
+        ]
+        .iter()
+        {
+            std::env::remove_var(name);
+        }
+        let parsed = Options::from_iter(&["cincinnati-graph-data"]);
+        assert_eq!(format!("{:?}", Options::default()), format!("{:?}", parsed));
+    }
+
+    #[test]
+    fn durations_take_a_unit() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(15 * 60));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(60 * 60));
+        assert!(parse_duration("1d").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("-1s").is_err());
+        assert!(parse_duration("18446744073709551615h").is_err());
+    }
+
+    #[test]
+    fn concurrency_is_at_least_one() {
+        assert_eq!(parse_concurrency("16").unwrap(), 16);
+        assert!(parse_concurrency("0").is_err());
+        assert!(parse_concurrency("many").is_err());
+    }
+
+    #[test]
+    fn outputs_write_to_stdout_without_a_path() {
+        let output: Output = "junit=report.xml".parse().unwrap();
+        assert_eq!(output.format, OutputFormat::Junit);
+        assert_eq!(output.path, Some(PathBuf::from("report.xml")));
+        assert_eq!("gha".parse::<Output>().unwrap().path, None);
+        assert_eq!("json=-".parse::<Output>().unwrap().path, None);
+        assert!("xml".parse::<Output>().is_err());
+    }
+
+    #[test]
+    fn skipped_checks_win_over_selected_ones() {
+        let opts = Options::from_iter(&[
+            "cincinnati-graph-data",
+            "--checks",
+            "dead-ends,baseline",
+            "--skip-checks",
+            "baseline",
+        ]);
+        assert!(opts.is_selected(Check::DeadEnds));
+        assert!(!opts.is_selected(Check::Baseline));
+        assert!(!opts.is_selected(Check::YamlSyntax));
+        assert!(Options::default().is_selected(Check::YamlSyntax));
+    }
+
+    #[test]
+    fn global_flags_follow_the_subcommand() {
+        let opts = Options::from_iter(&["cincinnati-graph-data", "verify-yaml", "--offline"]);
+        assert_eq!(opts.command(), Command::VerifyYaml);
+        assert!(opts.offline);
+        let staged = Options::from_iter(&["cincinnati-graph-data", "--staged"]);
+        assert_eq!(staged.command(), Command::VerifyYaml);
+        assert_eq!(Options::default().command(), Command::All);
+    }
+}
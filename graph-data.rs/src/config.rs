@@ -0,0 +1,159 @@
+//! Defaults for the command line options, read from a file at the root of the data directory.
+
+use crate::checks::{self, Check};
+use crate::cli::{self, Options};
+use anyhow::{Context, Result as Fallible};
+use semver::Version;
+use serde::Deserialize;
+use structopt::clap::ArgMatches;
+
+/// Name of the configuration file below the data directory.
+pub const FILE_NAME: &str = ".graph-data-checks.yaml";
+
+/// The options which may be configured, named like their flags.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct Config {
+    checks: Option<Vec<String>>,
+    skip_checks: Option<Vec<String>>,
+    strict: Option<bool>,
+    deny_unknown_fields: Option<bool>,
+    arches: Option<Vec<String>>,
+    tiers: Option<Vec<String>>,
+    terminal_versions: Option<Vec<Version>>,
+    rate_limit_retries: Option<u32>,
+    rate_limit_delay: Option<u64>,
+    registry: Option<String>,
+    repository: Option<String>,
+    http_timeout: Option<String>,
+    deadline: Option<String>,
+}
+
+fn parse_names(names: Vec<String>) -> Fallible<Vec<Check>> {
+    names.iter().map(|name| checks::parse_name(name)).collect()
+}
+
+/// Fill the options not given on the command line from the configuration file in `opts.data_dir`,
+/// if there is one.
+pub fn apply(opts: &mut Options, matches: &ArgMatches) -> Fallible<()> {
+    let path = opts.data_dir.join(FILE_NAME);
+    if !path.exists() {
+        return Ok(());
+    }
+    let contents = std::fs::read(&path).context(format!("Reading {:?}", path))?;
+    let config: Config =
+        serde_yaml::from_slice(&contents).context(format!("Deserializing {:?}", path))?;
+
+    let unset = |name: &str| matches.occurrences_of(name) == 0;
+    if let Some(names) = config.checks.filter(|_| unset("checks")) {
+        opts.checks = parse_names(names).context(format!("Parsing checks in {:?}", path))?;
+    }
+    if let Some(names) = config.skip_checks.filter(|_| unset("skip_checks")) {
+        opts.skip_checks =
+            parse_names(names).context(format!("Parsing skip-checks in {:?}", path))?;
+    }
+    if let Some(strict) = config.strict.filter(|_| unset("strict")) {
+        opts.strict = strict;
+    }
+    if let Some(deny) = config
+        .deny_unknown_fields
+        .filter(|_| unset("deny_unknown_fields"))
+    {
+        opts.deny_unknown_fields = deny;
+    }
+    if let Some(arches) = config.arches.filter(|_| unset("arches")) {
+        opts.arches = arches;
+    }
+    if let Some(tiers) = config.tiers.filter(|_| unset("tiers")) {
+        opts.tiers = tiers;
+    }
+    if let Some(versions) = config
+        .terminal_versions
+        .filter(|_| unset("terminal_versions"))
+    {
+        opts.terminal_versions = versions;
+    }
+    if let Some(retries) = config
+        .rate_limit_retries
+        .filter(|_| unset("rate_limit_retries"))
+    {
+        opts.rate_limit_retries = retries;
+    }
+    if let Some(delay) = config
+        .rate_limit_delay
+        .filter(|_| unset("rate_limit_delay"))
+    {
+        opts.rate_limit_delay = delay;
+    }
+    if let Some(registry) = config.registry.filter(|_| unset("registry")) {
+        opts.registry = Some(registry);
+    }
+    if let Some(repository) = config.repository.filter(|_| unset("repository")) {
+        opts.repository = Some(repository);
+    }
+    if let Some(timeout) = config.http_timeout.filter(|_| unset("http_timeout")) {
+        opts.http_timeout =
+            cli::parse_duration(&timeout).context(format!("Parsing http-timeout in {:?}", path))?;
+    }
+    if let Some(deadline) = config.deadline.filter(|_| unset("deadline")) {
+        opts.deadline = Some(
+            cli::parse_duration(&deadline).context(format!("Parsing deadline in {:?}", path))?,
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use structopt::StructOpt;
+
+    /// Parse `args` after `--data-dir` with a configuration file holding `config`.
+    fn load(name: &str, config: &str, args: &[&str]) -> Fallible<Options> {
+        let dir = std::env::temp_dir().join(format!(
+            "cincinnati-graph-data-config-{}-{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(FILE_NAME), config).unwrap();
+        let data_dir = dir.to_string_lossy().into_owned();
+        let mut argv = vec!["cincinnati-graph-data", "--data-dir", data_dir.as_str()];
+        argv.extend_from_slice(args);
+        let matches = Options::clap().get_matches_from(&argv);
+        let mut opts = Options::from_clap(&matches);
+        let result = apply(&mut opts, &matches).map(|()| opts);
+        std::fs::remove_dir_all(&dir).unwrap();
+        result
+    }
+
+    #[test]
+    fn flags_win_over_the_file() {
+        let config = "registry: registry.example.com\nrepository: ocp/release\n\
+                      http-timeout: 2m\ndeadline: 1h\nrate-limit-retries: 7\n";
+        let opts = load(
+            "flags",
+            config,
+            &["--rate-limit-retries", "1", "--deadline", "15m"],
+        )
+        .unwrap();
+        assert_eq!(opts.registry.as_deref(), Some("registry.example.com"));
+        assert_eq!(opts.repository.as_deref(), Some("ocp/release"));
+        assert_eq!(opts.http_timeout, Duration::from_secs(2 * 60));
+        assert_eq!(opts.rate_limit_retries, 1);
+        assert_eq!(opts.deadline, Some(Duration::from_secs(15 * 60)));
+
+        let opts = load("defaults", "strict: true\n", &[]).unwrap();
+        assert!(opts.strict);
+        assert_eq!(opts.registry, None);
+        assert_eq!(opts.http_timeout, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let error = load("unknown", "registy: registry.example.com\n", &[]).unwrap_err();
+        assert!(format!("{:#}", error).contains("unknown field `registy`"));
+        assert!(load("duration", "deadline: 1d\n", &[]).is_err());
+    }
+}
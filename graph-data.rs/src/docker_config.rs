@@ -0,0 +1,217 @@
+//! Registry credentials from a Docker-style `config.json` auth file.
+
+use anyhow::{Context, Result as Fallible};
+use serde_json::Value;
+use std::env;
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use tracing::{info, warn};
+
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Auth files to look for, in order of preference.
+fn auth_file_paths() -> Vec<PathBuf> {
+    let mut paths = vec![];
+    if let Some(path) = env::var_os("REGISTRY_AUTH_FILE") {
+        paths.push(PathBuf::from(path));
+    }
+    if let Some(dir) = env::var_os("DOCKER_CONFIG") {
+        paths.push(PathBuf::from(dir).join("config.json"));
+    }
+    if let Some(home) = env::var_os("HOME") {
+        paths.push(PathBuf::from(home).join(".docker").join("config.json"));
+    }
+    paths
+}
+
+/// Reduce a registry reference or auth file key like `https://quay.io/v1/` to `quay.io`.
+fn normalize_host(registry: &str) -> String {
+    let registry = registry
+        .trim_start_matches("https://")
+        .trim_start_matches("http://");
+    registry
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Look up credentials for `registry` in the first auth file which exists.
+///
+/// Only the registry's own host matches, so a mirror never receives the credentials of `quay.io`.
+pub fn lookup(registry: &str) -> Fallible<Option<Credentials>> {
+    for path in auth_file_paths() {
+        if !path.exists() {
+            continue;
+        }
+        let text = std::fs::read_to_string(&path).context(format!("Reading {:?}", path))?;
+        let config: Value =
+            serde_json::from_str(&text).context(format!("Parsing {:?} as JSON", path))?;
+        let credentials = from_config(&config, registry)
+            .context(format!("Reading credentials from {:?}", path))?;
+        if credentials.is_some() {
+            info!(
+                "Using {} credentials from {:?}",
+                normalize_host(registry),
+                path
+            );
+        }
+        return Ok(credentials);
+    }
+    Ok(None)
+}
+
+fn from_config(config: &Value, registry: &str) -> Fallible<Option<Credentials>> {
+    let host = normalize_host(registry);
+    let entry_for = |section: &str| {
+        config
+            .get(section)
+            .and_then(Value::as_object)
+            .and_then(|entries| entries.iter().find(|(key, _)| normalize_host(key) == host))
+            .map(|(_, entry)| entry)
+    };
+
+    if let Some(helper) = entry_for("credHelpers").and_then(Value::as_str) {
+        return Ok(from_helper_or_anonymous(helper, &host));
+    }
+    let auth = match entry_for("auths") {
+        Some(auth) => auth,
+        None => return Ok(None),
+    };
+    match auth.get("auth").and_then(Value::as_str) {
+        Some(encoded) => decode_auth(encoded).map(Some),
+        // Entries without `auth` are kept by the global credential store
+        None => match config.get("credsStore").and_then(Value::as_str) {
+            Some(helper) => Ok(from_helper_or_anonymous(helper, &host)),
+            None => Ok(None),
+        },
+    }
+}
+
+/// Decode a base64 `USERNAME:PASSWORD` auth field, without ever including it in errors.
+fn decode_auth(encoded: &str) -> Fallible<Credentials> {
+    let decoded = base64::decode(encoded).context("Decoding the base64 auth field")?;
+    let decoded = String::from_utf8(decoded).context("The auth field is not valid UTF-8")?;
+    let mut parts = decoded.splitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(username), Some(password)) => Ok(Credentials {
+            username: username.to_string(),
+            password: password.to_string(),
+        }),
+        _ => anyhow::bail!("The auth field is not in USERNAME:PASSWORD form"),
+    }
+}
+
+/// Like `from_helper`, but warn and fall back to anonymous access if the helper is missing or
+/// has no credentials for `host`.
+fn from_helper_or_anonymous(helper: &str, host: &str) -> Option<Credentials> {
+    match from_helper(helper, host) {
+        Ok(credentials) => Some(credentials),
+        Err(e) => {
+            warn!("{:#}, continuing without credentials", e);
+            None
+        }
+    }
+}
+
+/// Ask `docker-credential-<helper>` for the credentials of `host`.
+fn from_helper(helper: &str, host: &str) -> Fallible<Credentials> {
+    let program = format!("docker-credential-{}", helper);
+    let mut child = Command::new(&program)
+        .arg("get")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context(format!("Running {}", program))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(host.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!("{} found no credentials for {}", program, host);
+    }
+    let value: Value = serde_json::from_slice(&output.stdout)
+        .context(format!("Parsing the output of {}", program))?;
+    let field = |name: &str| {
+        value
+            .get(name)
+            .and_then(Value::as_str)
+            .map(String::from)
+            .ok_or_else(|| anyhow::anyhow!("{} returned no {}", program, name))
+    };
+    Ok(Credentials {
+        username: field("Username")?,
+        password: field("Secret")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn hosts_are_normalized() {
+        assert_eq!(normalize_host("https://Quay.io/v1/"), "quay.io");
+        assert_eq!(normalize_host("quay.io/openshift-release-dev"), "quay.io");
+        assert_eq!(normalize_host("localhost:5000"), "localhost:5000");
+    }
+
+    #[test]
+    fn auths_match_only_their_own_host() {
+        // user:pass
+        let config = json!({ "auths": { "https://quay.io/v1/": { "auth": "dXNlcjpwYXNz" } } });
+        let credentials = from_config(&config, "quay.io").unwrap().unwrap();
+        assert_eq!(credentials.username, "user");
+        assert_eq!(credentials.password, "pass");
+        assert!(from_config(&config, "mirror.example.com")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn invalid_auth_fields_are_errors_without_the_secret() {
+        // user, without a password
+        let config = json!({ "auths": { "quay.io": { "auth": "dXNlcg==" } } });
+        let e = from_config(&config, "quay.io").unwrap_err();
+        assert!(format!("{:#}", e).contains("USERNAME:PASSWORD"));
+        assert!(!format!("{:#}", e).contains("dXNlcg"));
+        let config = json!({ "auths": { "quay.io": { "auth": "not base64!" } } });
+        assert!(from_config(&config, "quay.io").is_err());
+    }
+
+    #[test]
+    fn missing_helpers_fall_back_to_anonymous_access() {
+        let helper = "graph-data-test-missing";
+        let config = json!({ "credHelpers": { "quay.io": helper } });
+        assert!(from_config(&config, "quay.io").unwrap().is_none());
+        let config = json!({ "auths": { "quay.io": {} }, "credsStore": helper });
+        assert!(from_config(&config, "quay.io").unwrap().is_none());
+    }
+
+    #[test]
+    fn debug_redacts_the_password() {
+        let credentials = Credentials {
+            username: "user".to_string(),
+            password: "hunter2".to_string(),
+        };
+        let debug = format!("{:?}", credentials);
+        assert!(debug.contains("user"));
+        assert!(!debug.contains("hunter2"));
+    }
+}
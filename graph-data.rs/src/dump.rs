@@ -0,0 +1,87 @@
+//! A normalized text rendering of the parsed graph data, for reviewing the semantic effect of a change.
+
+use cincinnati::plugins::internal::openshift_secondary_metadata_parser::plugin::graph_data_model::{BlockedEdge, Channel};
+use semver::{Identifier, Version};
+use std::cmp::Ordering;
+use std::path::PathBuf;
+
+/// The architecture of versions without one.
+const DEFAULT_ARCH: &str = "amd64";
+
+/// `version` without a `+amd64` suffix, which only spells out the default architecture.
+fn normalize(version: &Version) -> Version {
+    let mut version = version.clone();
+    if version.build == [Identifier::AlphaNumeric(DEFAULT_ARCH.to_string())] {
+        version.build.clear();
+    }
+    version
+}
+
+/// Order by semver, breaking ties such as `4.2.14` and `4.2.14+amd64` by their text.
+fn cmp_versions(a: &Version, b: &Version) -> Ordering {
+    a.cmp(b).then_with(|| a.to_string().cmp(&b.to_string()))
+}
+
+/// Render channels sorted by name with sorted versions, followed by blocked edges sorted by target.
+///
+/// The output only depends on the parsed content, not on file names or the order of entries.
+/// Versions are normalized by dropping a `+amd64` suffix, so `4.2.14` and `4.2.14+amd64` render
+/// the same and are listed once per channel; other architectures are kept.
+pub fn render(channels: &[(PathBuf, Channel)], edges: &[(PathBuf, BlockedEdge)]) -> String {
+    let mut out = String::new();
+
+    let mut channels: Vec<&Channel> = channels.iter().map(|(_, channel)| channel).collect();
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+    for channel in channels {
+        out.push_str(&format!("channel {}\n", channel.name));
+        let mut versions: Vec<Version> = channel.versions.iter().map(normalize).collect();
+        versions.sort_by(cmp_versions);
+        versions.dedup_by(|a, b| cmp_versions(a, b) == Ordering::Equal);
+        for version in versions {
+            out.push_str(&format!("  {}\n", version));
+        }
+    }
+
+    let mut edges: Vec<(Version, String)> = edges
+        .iter()
+        .map(|(_, edge)| (normalize(&edge.to), edge.from.as_str().trim().to_string()))
+        .collect();
+    edges.sort_by(|a, b| cmp_versions(&a.0, &b.0).then_with(|| a.1.cmp(&b.1)));
+    for (to, from) in edges {
+        out.push_str(&format!("blocked-edge {} from {}\n", to, from));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_ignores_order_and_the_default_architecture() {
+        let channel =
+            |path: &str, yaml: &str| (PathBuf::from(path), serde_yaml::from_str(yaml).unwrap());
+        let edge = |yaml: &str| {
+            (
+                PathBuf::from("edge.yaml"),
+                serde_yaml::from_str(yaml).unwrap(),
+            )
+        };
+        let channels: Vec<(PathBuf, Channel)> = vec![
+            channel(
+                "b.yaml",
+                "name: stable-4.5\nversions: [4.5.2+amd64, 4.5.1, 4.5.2]\n",
+            ),
+            channel("a.yaml", "name: fast-4.5\nversions: [4.5.2+s390x]\n"),
+        ];
+        let edges: Vec<(PathBuf, BlockedEdge)> = vec![
+            edge("to: 4.5.2\nfrom: ' 4\\.5\\.1 '\n"),
+            edge("to: 4.5.1+amd64\nfrom: .*\n"),
+        ];
+        assert_eq!(
+            render(&channels, &edges),
+            "channel fast-4.5\n  4.5.2+s390x\nchannel stable-4.5\n  4.5.1\n  4.5.2\n\
+             blocked-edge 4.5.1 from .*\nblocked-edge 4.5.2 from 4\\.5\\.1\n"
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! A summary of the run posted as a comment on a GitHub pull request.
+
+use crate::cli::Options;
+use crate::report::{RunReport, Severity};
+use anyhow::{Context, Result as Fallible};
+use serde_json::{json, Value};
+use tracing::info;
+
+const API_URL: &str = "https://api.github.com";
+
+/// Marks the comment this tool posted, so later runs update it instead of adding another.
+const MARKER: &str = "<!-- cincinnati-graph-data -->";
+
+/// Render the comment: a table of the checks, then the findings of each check in a collapsed
+/// `<details>` block.
+fn comment_body(summary: &RunReport) -> String {
+    let failed = summary.exit_code(false) != 0;
+    let mut body = format!(
+        "{}\n### Graph data checks {}\n\n| check | status | errors | warnings |\n|---|---|---|---|\n",
+        MARKER,
+        if failed { "failed" } else { "passed" }
+    );
+    for check in summary.checks.iter() {
+        body.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            check.name,
+            check.status(),
+            check.errors,
+            check.warnings
+        ));
+    }
+    for check in summary.checks.iter() {
+        let findings: Vec<_> = summary
+            .findings
+            .iter()
+            .filter(|finding| finding.suite == check.suite && finding.check == check.name)
+            .filter(|finding| finding.severity != Severity::Info)
+            .collect();
+        if findings.is_empty() {
+            continue;
+        }
+        body.push_str(&format!(
+            "\n<details><summary>{} ({})</summary>\n\n",
+            check.name,
+            findings.len()
+        ));
+        for finding in findings {
+            body.push_str(&format!("- **{}**", finding.severity.name()));
+            if finding.message.contains('\n') {
+                // Inline code can't span lines, which snippets pointing at a column need
+                body.push_str(&format!(
+                    "\n\n  ```\n  {}\n  ```\n",
+                    finding.message.replace("```", "'''").replace('\n', "\n  ")
+                ));
+            } else {
+                body.push_str(&format!(" `{}`\n", finding.message.replace('`', "'")));
+            }
+        }
+        body.push_str("\n</details>\n");
+    }
+    body
+}
+
+/// The URL of the next page from the `Link` header of a paginated response, if there is one.
+fn next_page(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    let link = headers.get(reqwest::header::LINK)?.to_str().ok()?;
+    link.split(',').find_map(|link| {
+        let mut parts = link.split(';').map(str::trim);
+        let url = parts.next()?;
+        if parts.any(|param| param == "rel=\"next\"") {
+            Some(
+                url.trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string(),
+            )
+        } else {
+            None
+        }
+    })
+}
+
+fn request(
+    client: &reqwest::Client,
+    method: reqwest::Method,
+    url: &str,
+    token: &str,
+) -> reqwest::RequestBuilder {
+    client
+        .request(method, url)
+        .header("Authorization", format!("token {}", token))
+        .header("Accept", "application/vnd.github.v3+json")
+}
+
+/// Post the summary on the pull request `opts.pr` unless `--offline`, or update the comment an
+/// earlier run posted.
+pub async fn comment(
+    client: &reqwest::Client,
+    opts: &Options,
+    summary: &RunReport,
+) -> Fallible<()> {
+    let pr = match opts.pr {
+        Some(pr) if !opts.offline => pr,
+        _ => return Ok(()),
+    };
+    let token = opts
+        .github_token
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("--pr needs a token from --github-token"))?;
+    let repo = opts
+        .github_repo
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("--pr needs a repository from --github-repo"))?;
+
+    let comments_url = format!("{}/repos/{}/issues/{}/comments", API_URL, repo, pr);
+    let mut comments: Vec<Value> = vec![];
+    let mut page = Some(format!("{}?per_page=100", comments_url));
+    while let Some(url) = page {
+        let response = request(client, reqwest::Method::GET, &url, token)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .context(format!("Listing the comments on {}#{}", repo, pr))?;
+        page = next_page(response.headers());
+        let mut page_comments: Vec<Value> =
+            response.json().await.context("Parsing the comments")?;
+        comments.append(&mut page_comments);
+    }
+    let previous = comments.iter().find_map(|comment| {
+        let body = comment.get("body").and_then(Value::as_str)?;
+        if body.starts_with(MARKER) {
+            comment.get("id").and_then(Value::as_u64)
+        } else {
+            None
+        }
+    });
+
+    let body = json!({ "body": comment_body(summary) });
+    let (method, url) = match previous {
+        Some(id) => (
+            reqwest::Method::PATCH,
+            format!("{}/repos/{}/issues/comments/{}", API_URL, repo, id),
+        ),
+        None => (reqwest::Method::POST, comments_url),
+    };
+    request(client, method, &url, token)
+        .json(&body)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .context(format!("Commenting on {}#{}", repo, pr))?;
+    info!("Commented on {}#{}", repo, pr);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Report;
+    use reqwest::header::{HeaderMap, HeaderValue, LINK};
+
+    #[test]
+    fn next_page_follows_the_link_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(next_page(&headers), None);
+        headers.insert(
+            LINK,
+            HeaderValue::from_static(
+                "<https://api.github.com/repositories/1/issues/2/comments?page=1>; rel=\"prev\", \
+                 <https://api.github.com/repositories/1/issues/2/comments?page=3>; rel=\"next\"",
+            ),
+        );
+        assert_eq!(
+            next_page(&headers),
+            Some("https://api.github.com/repositories/1/issues/2/comments?page=3".to_string())
+        );
+        headers.insert(
+            LINK,
+            HeaderValue::from_static("<https://api.github.com/x?page=1>; rel=\"first\""),
+        );
+        assert_eq!(next_page(&headers), None);
+    }
+
+    #[test]
+    fn multi_line_findings_are_fenced() {
+        let report = Report::default();
+        report.finding(
+            "verify_yaml",
+            "yaml-syntax",
+            Severity::Error,
+            "invalid type\n 2 | versions: ```\n   |           ^",
+        );
+        report.finding(
+            "verify_yaml",
+            "yaml-syntax",
+            Severity::Error,
+            "`fast-4.5.yaml` repeats keys",
+        );
+        report.time_check("verify_yaml", "yaml-syntax", Default::default());
+        let body = comment_body(&report.summary());
+        assert!(body.starts_with(MARKER));
+        assert!(body.contains("| yaml-syntax | fail | 2 | 0 |\n"));
+        assert!(body.contains(
+            "- **error**\n\n  ```\n  invalid type\n   2 | versions: '''\n     |           ^\n  ```\n"
+        ));
+        assert!(body.contains("- **error** `'fast-4.5.yaml' repeats keys`\n"));
+    }
+}
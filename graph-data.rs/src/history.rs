@@ -0,0 +1,339 @@
+//! Comparison of channels against their contents at an earlier git revision.
+
+use crate::channels;
+use crate::report::Problem;
+use anyhow::{Context, Result as Fallible};
+use cincinnati::plugins::internal::openshift_secondary_metadata_parser::plugin::graph_data_model::{BlockedEdge, Channel};
+use semver::Version;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A git command run in `dir`, with messages in English whatever the user's locale.
+fn git(dir: &Path) -> Command {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir).env("LC_ALL", "C");
+    command
+}
+
+/// The contents of `path` at git revision `rev`, or `None` if the file did not exist then.
+fn show(rev: &str, path: &Path) -> Fallible<Option<Vec<u8>>> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("{:?} has no file name", path))?;
+    let object = format!("{}:./{}", rev, name.to_string_lossy());
+    let exists = git(dir)
+        .args(&["cat-file", "-e", &object])
+        .output()
+        .context("Running git cat-file")?
+        .status
+        .success();
+    if !exists {
+        // The file is only missing if the revision itself is fine
+        let commit = format!("{}^{{commit}}", rev);
+        let output = git(dir)
+            .args(&["rev-parse", "--verify", "--quiet", &commit])
+            .output()
+            .context("Running git rev-parse")?;
+        if output.status.success() {
+            return Ok(None);
+        }
+        anyhow::bail!("{} is not a git revision", rev);
+    }
+    let output = git(dir)
+        .arg("show")
+        .arg(&object)
+        .output()
+        .context("Running git show")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git show {}:{:?} failed: {}",
+            rev,
+            path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(Some(output.stdout))
+}
+
+/// Run git in `dir` with `args`, joining each path it prints onto `dir`.
+fn git_paths(dir: &Path, args: &[&str]) -> Fallible<HashSet<PathBuf>> {
+    let output = git(dir).args(args).output().context("Running git")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| dir.join(line))
+        .collect())
+}
+
+/// The files below `dir` which are staged in git's index.
+pub fn staged_files(dir: &Path) -> Fallible<HashSet<PathBuf>> {
+    git_paths(dir, &["diff", "--cached", "--name-only", "--relative"])
+}
+
+/// The versions `channels` and `edges` mentioned at `rev`, skipping files which did not exist then.
+pub fn versions_at(
+    channels: &[(PathBuf, Channel)],
+    edges: &[(PathBuf, BlockedEdge)],
+    rev: &str,
+) -> Fallible<HashSet<Version>> {
+    let mut versions = HashSet::new();
+    for (path, _) in channels.iter() {
+        if let Some(bytes) = show(rev, path)? {
+            let previous: Channel =
+                serde_yaml::from_slice(&bytes).context(format!("Parsing {:?} at {}", path, rev))?;
+            versions.extend(previous.versions);
+        }
+    }
+    for (path, _) in edges.iter() {
+        if let Some(bytes) = show(rev, path)? {
+            let previous: BlockedEdge =
+                serde_yaml::from_slice(&bytes).context(format!("Parsing {:?} at {}", path, rev))?;
+            versions.insert(previous.to);
+        }
+    }
+    Ok(versions)
+}
+
+/// The blocked edges of `edges` which were added or retargeted since `rev`.
+fn edges_since<'a>(
+    edges: &'a [(PathBuf, BlockedEdge)],
+    rev: &str,
+) -> Fallible<Vec<&'a BlockedEdge>> {
+    let mut added = vec![];
+    for (path, edge) in edges.iter() {
+        let previous: Option<BlockedEdge> = match show(rev, path)? {
+            Some(bytes) => Some(
+                serde_yaml::from_slice(&bytes).context(format!("Parsing {:?} at {}", path, rev))?,
+            ),
+            None => None,
+        };
+        if previous.map_or(true, |previous| {
+            previous.to.to_string() != edge.to.to_string()
+        }) {
+            added.push(edge);
+        }
+    }
+    Ok(added)
+}
+
+/// Report versions dropped from a `tier` channel since `rev` without blocking an edge to them in
+/// the same change, i.e. a blocked edge added since `rev`.
+///
+/// Channels which did not exist at `rev` are skipped.
+pub fn check_monotonic(
+    channels: &[(PathBuf, Channel)],
+    edges: &[(PathBuf, BlockedEdge)],
+    rev: &str,
+    tier: &str,
+) -> Fallible<Vec<Problem>> {
+    let added_edges = edges_since(edges, rev)?;
+    let mut errors = vec![];
+    for (path, channel) in channels.iter() {
+        match channels::split_name(&channel.name) {
+            Some((channel_tier, _)) if channel_tier == tier => {}
+            _ => continue,
+        }
+        let previous: Channel = match show(rev, path)? {
+            Some(bytes) => {
+                serde_yaml::from_slice(&bytes).context(format!("Parsing {:?} at {}", path, rev))?
+            }
+            None => continue,
+        };
+        let current: HashSet<String> = channel.versions.iter().map(|v| v.to_string()).collect();
+        for version in previous.versions.iter() {
+            if current.contains(&version.to_string()) {
+                continue;
+            }
+            let blocked = added_edges.iter().any(|edge| {
+                edge.to == *version && (edge.to.build.is_empty() || edge.to.build == version.build)
+            });
+            if !blocked {
+                errors.push(
+                    Problem::new(format!(
+                        "{:?}: {} dropped {} since {} without blocking it",
+                        path, channel.name, version, rev
+                    ))
+                    .in_file(path)
+                    .about(version),
+                );
+            }
+        }
+    }
+    Ok(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A git repository below the system's temporary directory with `files` committed.
+    fn repository(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "cincinnati-graph-data-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("channels")).unwrap();
+        std::fs::create_dir_all(dir.join("blocked-edges")).unwrap();
+        run(&dir, &["init", "--quiet"]);
+        for (path, contents) in files.iter() {
+            std::fs::write(dir.join(path), contents).unwrap();
+        }
+        run(&dir, &["add", "."]);
+        run(
+            &dir,
+            &[
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@example.com",
+                "-c",
+                "commit.gpgsign=false",
+                "commit",
+                "--quiet",
+                "-m",
+                "graph data",
+            ],
+        );
+        dir
+    }
+
+    fn run(dir: &Path, args: &[&str]) {
+        let status = git(dir).args(args).status().unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    /// Write `contents` to `path` below `dir` and parse it.
+    fn write<T: serde::de::DeserializeOwned>(
+        dir: &Path,
+        path: &str,
+        contents: &str,
+    ) -> (PathBuf, T) {
+        let path = dir.join(path);
+        std::fs::write(&path, contents).unwrap();
+        (path, serde_yaml::from_str(contents).unwrap())
+    }
+
+    #[test]
+    fn dropped_versions_need_a_new_blocked_edge() {
+        let dir = repository(
+            "monotonic",
+            &[
+                (
+                    "channels/stable-4.5.yaml",
+                    "name: stable-4.5\nversions: [4.5.1, 4.5.2]\n",
+                ),
+                (
+                    "channels/fast-4.5.yaml",
+                    "name: fast-4.5\nversions: [4.5.1, 4.5.2]\n",
+                ),
+                ("blocked-edges/4.5.2.yaml", "to: 4.5.2\nfrom: 4\\.4\\..*\n"),
+            ],
+        );
+        let channels: Vec<(PathBuf, Channel)> = vec![
+            write(
+                &dir,
+                "channels/stable-4.5.yaml",
+                "name: stable-4.5\nversions: [4.5.1]\n",
+            ),
+            write(
+                &dir,
+                "channels/fast-4.5.yaml",
+                "name: fast-4.5\nversions: [4.5.1]\n",
+            ),
+        ];
+        let mut edges: Vec<(PathBuf, BlockedEdge)> = vec![(
+            dir.join("blocked-edges/4.5.2.yaml"),
+            serde_yaml::from_str("to: 4.5.2\nfrom: 4\\.4\\..*\n").unwrap(),
+        )];
+
+        // The edge to 4.5.2 was blocked before, and fast isn't the most stable tier
+        let problems = check_monotonic(&channels, &edges, "HEAD", "stable").unwrap();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].file, Some(dir.join("channels/stable-4.5.yaml")));
+        assert_eq!(problems[0].version, Some(Version::parse("4.5.2").unwrap()));
+
+        edges.push(write(
+            &dir,
+            "blocked-edges/4.5.2-from-4.5.yaml",
+            "to: 4.5.2\nfrom: 4\\.5\\..*\n",
+        ));
+        assert!(check_monotonic(&channels, &edges, "HEAD", "stable")
+            .unwrap()
+            .is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn versions_at_reads_the_revision() {
+        let dir = repository(
+            "versions-at",
+            &[(
+                "channels/fast-4.5.yaml",
+                "name: fast-4.5\nversions: [4.5.1]\n",
+            )],
+        );
+        let channels: Vec<(PathBuf, Channel)> = vec![
+            write(
+                &dir,
+                "channels/fast-4.5.yaml",
+                "name: fast-4.5\nversions: [4.5.1, 4.5.2]\n",
+            ),
+            write(
+                &dir,
+                "channels/fast-4.6.yaml",
+                "name: fast-4.6\nversions: [4.6.1]\n",
+            ),
+        ];
+        let versions = versions_at(&channels, &[], "HEAD").unwrap();
+        let expected: HashSet<Version> =
+            vec![Version::parse("4.5.1").unwrap()].into_iter().collect();
+        assert_eq!(versions, expected);
+
+        let e = versions_at(&channels, &[], "no-such-branch").unwrap_err();
+        assert_eq!(e.to_string(), "no-such-branch is not a git revision");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn staged_files_are_relative_to_the_directory() {
+        let dir = repository(
+            "staged",
+            &[(
+                "channels/fast-4.5.yaml",
+                "name: fast-4.5\nversions: [4.5.1]\n",
+            )],
+        );
+        std::fs::write(
+            dir.join("channels/fast-4.6.yaml"),
+            "name: fast-4.6\nversions: [4.6.1]\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("channels/fast-4.5.yaml"),
+            "name: fast-4.5\nversions: [4.5.1, 4.5.2]\n",
+        )
+        .unwrap();
+        run(&dir, &["add", "channels/fast-4.6.yaml"]);
+
+        let staged = staged_files(&dir.join("channels")).unwrap();
+        let expected: HashSet<PathBuf> = vec![dir.join("channels/fast-4.6.yaml")]
+            .into_iter()
+            .collect();
+        assert_eq!(staged, expected);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,244 @@
+//! The HTTP client shared by the integrations posting the results of a run.
+
+use crate::cli::Options;
+use anyhow::{Context, Result as Fallible};
+use std::env;
+
+/// Overrides the user-agent sent with every request.
+pub const USER_AGENT_ENV: &str = "GRAPH_DATA_USER_AGENT";
+
+/// The user-agent to send, `cincinnati-graph-data/<version>` unless overridden.
+fn user_agent(var: &impl Fn(&str) -> Option<String>) -> String {
+    var(USER_AGENT_ENV)
+        .unwrap_or_else(|| format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")))
+}
+
+/// Proxy variables, and the scheme of the requests sent through each.
+const PROXY_ENVS: [(&str, &str); 2] = [("HTTPS_PROXY", "https"), ("HTTP_PROXY", "http")];
+
+/// Build the client, configured once for every integration.
+pub fn client(opts: &Options) -> Fallible<reqwest::Client> {
+    builder(opts, |name| env::var(name).ok())?
+        .build()
+        .context("Building the HTTP client")
+}
+
+/// The configuration of `client`, reading the environment through `var`.
+///
+/// The proxies are read here rather than by reqwest, which ignores invalid ones.
+fn builder(
+    opts: &Options,
+    var: impl Fn(&str) -> Option<String>,
+) -> Fallible<reqwest::ClientBuilder> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(opts.http_timeout)
+        .user_agent(user_agent(&var))
+        .no_proxy();
+    for (name, scheme) in PROXY_ENVS.iter() {
+        let url = match var(name).or_else(|| var(&name.to_lowercase())) {
+            Some(url) => url,
+            None => continue,
+        };
+        let proxy = match *scheme {
+            "https" => reqwest::Proxy::https(&url),
+            _ => reqwest::Proxy::http(&url),
+        };
+        builder = builder.proxy(proxy.context(format!("Invalid proxy URL in {}", name))?);
+    }
+    if let Some(path) = &opts.ca_bundle {
+        let bundle =
+            std::fs::read_to_string(path).context(format!("Reading the CA bundle {:?}", path))?;
+        let certificates = certificates(&bundle);
+        if certificates.is_empty() {
+            anyhow::bail!("No certificates in the CA bundle {:?}", path);
+        }
+        for certificate in certificates {
+            let certificate = reqwest::Certificate::from_pem(certificate.as_bytes())
+                .context(format!("Parsing the CA bundle {:?}", path))?;
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    Ok(builder)
+}
+
+/// The PEM certificates of `bundle`, which reqwest only parses one at a time.
+fn certificates(bundle: &str) -> Vec<&str> {
+    const END: &str = "-----END CERTIFICATE-----";
+    let mut certificates = vec![];
+    let mut rest = bundle;
+    while let Some(end) = rest.find(END) {
+        let start = rest[..end]
+            .find("-----BEGIN CERTIFICATE-----")
+            .unwrap_or(end);
+        certificates.push(&rest[start..end + END.len()]);
+        rest = &rest[end + END.len()..];
+    }
+    certificates
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    /// An empty successful response.
+    pub(crate) const OK: &str = "HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+
+    /// Answer a connection with each of `responses` in turn on a local port, returning its URL
+    /// and the head of every request it got.
+    pub(crate) fn serve(responses: Vec<&'static str>) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, heads) = mpsc::channel();
+        thread::spawn(move || {
+            for (response, stream) in responses.into_iter().zip(listener.incoming()) {
+                let mut stream = stream.unwrap();
+                let mut head = vec![];
+                let mut byte = [0; 1];
+                while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap() == 1 {
+                    head.push(byte[0]);
+                }
+                sender
+                    .send(String::from_utf8_lossy(&head).into_owned())
+                    .unwrap();
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (url, heads)
+    }
+
+    #[test]
+    fn requests_carry_the_user_agent() {
+        let (url, heads) = serve(vec![OK, OK]);
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let client = builder(&Options::default(), |_| None)
+                .unwrap()
+                .build()
+                .unwrap();
+            client.get(&url).send().await.unwrap();
+            let overridden = builder(&Options::default(), |name| match name {
+                USER_AGENT_ENV => Some("mirror-check/1".to_string()),
+                _ => None,
+            })
+            .unwrap()
+            .build()
+            .unwrap();
+            overridden.get(&url).send().await.unwrap();
+        });
+
+        let head = heads.recv().unwrap().to_lowercase();
+        assert!(head.contains(&format!(
+            "\r\nuser-agent: {}/{}\r\n",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        )));
+        let head = heads.recv().unwrap().to_lowercase();
+        assert!(head.contains("\r\nuser-agent: mirror-check/1\r\n"));
+    }
+
+    #[test]
+    fn slow_responses_time_out() {
+        // Accepts the connection but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let opts = Options {
+            http_timeout: Duration::from_millis(200),
+            ..Options::default()
+        };
+        let client = client(&opts).unwrap();
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let error = runtime.block_on(client.get(&url).send()).unwrap_err();
+        assert!(error.is_timeout());
+        drop(listener);
+    }
+
+    #[test]
+    fn requests_go_through_the_proxy() {
+        let (url, heads) = serve(vec![OK]);
+        let client = builder(&Options::default(), |name| match name {
+            "http_proxy" => Some(url.clone()),
+            _ => None,
+        })
+        .unwrap()
+        .build()
+        .unwrap();
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(client.get("http://registry.example.com/v2/").send())
+            .unwrap();
+
+        let head = heads.recv().unwrap();
+        assert!(head.starts_with("GET http://registry.example.com/v2/ HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn invalid_proxies_are_errors() {
+        let error = builder(&Options::default(), |name| match name {
+            "HTTPS_PROXY" => Some("http://proxy example:3128".to_string()),
+            _ => None,
+        })
+        .unwrap_err();
+        assert_eq!(error.to_string(), "Invalid proxy URL in HTTPS_PROXY");
+    }
+
+    /// A self-signed certificate for `graph-data-test`.
+    const CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBizCCATGgAwIBAgIUEPNnCMebb0PeVXoOyU1mv/UXUF0wCgYIKoZIzj0EAwIw
+GjEYMBYGA1UEAwwPZ3JhcGgtZGF0YS10ZXN0MCAXDTI2MTAxNDA4MDExMFoYDzIx
+MjYwOTIwMDgwMTEwWjAaMRgwFgYDVQQDDA9ncmFwaC1kYXRhLXRlc3QwWTATBgcq
+hkjOPQIBBggqhkjOPQMBBwNCAATcNVqpAPgsdHSrHOtq11l9EDXAGajAlFbPyPjm
+V++AOz8By/o5WJOIO4hxO4CWNLCFqfAWMXd/b5ORsCoPWbhMo1MwUTAdBgNVHQ4E
+FgQU1cKWg0gkWhIq208bx8hvj87WObowHwYDVR0jBBgwFoAU1cKWg0gkWhIq208b
+x8hvj87WObowDwYDVR0TAQH/BAUwAwEB/zAKBggqhkjOPQQDAgNIADBFAiBnTCkF
+kwdRWoKL8m4unFnT1B0mHzTsPKM3mlUrJBGb1gIhAP55UEPBSkZ80jqUIJO2+6K3
+69OfQW73lJEw5qf+uULu
+-----END CERTIFICATE-----";
+
+    #[test]
+    fn ca_bundles_are_loaded() {
+        let dir = std::env::temp_dir().join(format!(
+            "cincinnati-graph-data-ca-bundle-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bundle = dir.join("bundle.pem");
+        std::fs::write(&bundle, format!("{}\n{}\n", CERTIFICATE, CERTIFICATE)).unwrap();
+        assert_eq!(
+            certificates(&std::fs::read_to_string(&bundle).unwrap()).len(),
+            2
+        );
+        let opts = Options {
+            ca_bundle: Some(bundle.clone()),
+            ..Options::default()
+        };
+        assert!(client(&opts).is_ok());
+
+        std::fs::write(&bundle, "not a certificate\n-----END CERTIFICATE-----\n").unwrap();
+        let error = client(&opts).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("Parsing the CA bundle {:?}", bundle)
+        );
+        std::fs::write(&bundle, "").unwrap();
+        assert_eq!(
+            client(&opts).unwrap_err().to_string(),
+            format!("No certificates in the CA bundle {:?}", bundle)
+        );
+        let opts = Options {
+            ca_bundle: Some(dir.join("missing.pem")),
+            ..Options::default()
+        };
+        assert!(client(&opts)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Reading the CA bundle"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,154 @@
+//! Validation of the Cincinnati graph data: the channel and blocked edge files, and the
+//! presence of every mentioned release in the release registry.
+//!
+//! The registry check and everything else which needs the network live behind the `registry`
+//! feature, and the command line tool's own dependencies behind the `cli` feature, which also
+//! enables `registry`. Both are enabled by default; library users validating the YAML only can
+//! depend on this crate with `default-features = false`.
+//!
+//! The functions take a `cli::Options`, whose `Default` is a run without any flags:
+//!
+//! ```no_run
+//! use cincinnati_graph_data::{cli::Options, report::Report};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let opts = Options {
+//!     data_dir: "/srv/graph-data".into(),
+//!     ..Options::default()
+//! };
+//! let report = Report::default();
+//! let found_versions = cincinnati_graph_data::validate_yaml(&opts, &report).await?;
+//! cincinnati_graph_data::check_releases(&opts, &found_versions.all(), &report).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod baseline;
+mod channels;
+#[cfg(feature = "registry")]
+mod check_releases;
+pub mod checks;
+pub mod cli;
+mod config;
+#[cfg(feature = "registry")]
+mod docker_config;
+mod dump;
+#[cfg(feature = "registry")]
+mod github;
+mod history;
+#[cfg(feature = "registry")]
+mod http;
+#[cfg(feature = "registry")]
+mod metrics;
+pub mod report;
+pub mod validators;
+mod verify_yaml;
+#[cfg(feature = "registry")]
+mod webhook;
+
+pub use verify_yaml::{ArchConvention, FoundVersions, Minor, Shard, Target, VersionFilter};
+
+use anyhow::Result as Fallible;
+use semver::Version;
+#[cfg(feature = "registry")]
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Parse and validate all graph data files under `opts.data_dir`, returning the versions they mention.
+pub async fn validate_yaml(
+    opts: &cli::Options,
+    report: &report::Report,
+) -> Fallible<FoundVersions> {
+    validate_yaml_with(opts, report, &validators::Custom::default()).await
+}
+
+/// Like `validate_yaml`, but also checking every file with the validators of `custom`.
+pub async fn validate_yaml_with(
+    opts: &cli::Options,
+    report: &report::Report,
+    custom: &validators::Custom,
+) -> Fallible<FoundVersions> {
+    verify_yaml::run(opts, report, custom).await
+}
+
+/// Parse the graph data files under `opts.data_dir` and return the versions they mention, without
+/// validating them further.
+pub async fn collect_versions(opts: &cli::Options) -> Fallible<FoundVersions> {
+    verify_yaml::collect(opts).await
+}
+
+/// A CSV of the version and architecture of every release in the channels, against the channels.
+pub async fn version_matrix(opts: &cli::Options) -> Fallible<String> {
+    verify_yaml::matrix(opts).await
+}
+
+/// The directories below `opts.data_dir` holding the files `validate_yaml` reads.
+pub fn data_dirs(opts: &cli::Options) -> Vec<PathBuf> {
+    verify_yaml::data_dirs(opts)
+}
+
+/// Check that every one of `found_versions` has been published to the release registry.
+#[cfg(feature = "registry")]
+pub async fn check_releases(
+    opts: &cli::Options,
+    found_versions: &HashSet<Version>,
+    report: &report::Report,
+) -> Fallible<()> {
+    check_releases::run(opts, found_versions, report).await
+}
+
+/// Print why `version` passes or fails the checks, consulting the registry if the feature is enabled.
+pub async fn explain(opts: &cli::Options, version: &Version) -> Fallible<()> {
+    verify_yaml::explain(opts, version).await?;
+    #[cfg(feature = "registry")]
+    {
+        if !opts.offline {
+            check_releases::explain(opts, version).await?;
+        }
+    }
+    Ok(())
+}
+
+/// The HTTP client for `comment_on_pr`, `push_metrics` and `notify_failure`, with the timeout
+/// `--http-timeout`, the proxies of `HTTPS_PROXY` and `HTTP_PROXY`, the certificate authorities of
+/// `--ca-bundle` and a user-agent which `GRAPH_DATA_USER_AGENT` overrides.
+#[cfg(feature = "registry")]
+pub fn http_client(opts: &cli::Options) -> Fallible<reqwest::Client> {
+    http::client(opts)
+}
+
+/// Post `summary` as a comment on the pull request given with `--pr`, if any.
+#[cfg(feature = "registry")]
+pub async fn comment_on_pr(
+    client: &reqwest::Client,
+    opts: &cli::Options,
+    summary: &report::RunReport,
+) -> Fallible<()> {
+    github::comment(client, opts, summary).await
+}
+
+/// Push the metrics of `report` to the Prometheus Pushgateway at `url`.
+#[cfg(feature = "registry")]
+pub async fn push_metrics(
+    client: &reqwest::Client,
+    url: &str,
+    report: &report::Report,
+) -> Fallible<()> {
+    metrics::push(client, url, report.to_prometheus()).await
+}
+
+/// Post a summary of the failed run `summary` to the webhook at `url`.
+#[cfg(feature = "registry")]
+pub async fn notify_failure(
+    client: &reqwest::Client,
+    url: &str,
+    summary: &report::RunReport,
+) -> Fallible<()> {
+    webhook::notify(client, url, summary).await
+}
+
+/// Time a full registry scrape with each of the given fetch concurrencies.
+#[cfg(feature = "registry")]
+pub async fn bench_scrape(opts: &cli::Options, concurrencies: &[usize]) -> Fallible<()> {
+    check_releases::bench(opts, concurrencies).await
+}
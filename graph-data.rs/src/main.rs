@@ -1,14 +1,500 @@
-mod check_releases;
-mod verify_yaml;
-use anyhow::Result as Fallible;
+use anyhow::{Context, Result as Fallible};
+use cincinnati_graph_data::checks::Check;
+use cincinnati_graph_data::cli;
+use cincinnati_graph_data::report::{Counter, Report, RunReport, EXIT_DEADLINE};
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use semver::Version;
+use std::future::Future;
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tracing::{info, Level};
 
-async fn run_all_tests() -> Fallible<()> {
-    let found_versions = verify_yaml::run().await?;
-    check_releases::run(&found_versions).await?;
+/// Exit code used when the run is interrupted with Ctrl-C.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Why phases which need the network are skipped with `--offline`.
+const OFFLINE_REASON: &str = "needs the network, skipped by --offline";
+
+/// Why phases which hadn't finished when the `--deadline` passed didn't run.
+const DEADLINE_REASON: &str = "not run before the deadline";
+
+/// How long to wait for further changes before re-running in watch mode.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Run one phase, recording its duration and outcome in `report`.
+async fn run_phase<T>(
+    report: &Report,
+    name: &'static str,
+    phase: impl Future<Output = Fallible<T>>,
+) -> Option<T> {
+    let start = Instant::now();
+    let result = phase.await;
+    report.finish_phase(name, start.elapsed(), result.as_ref().err());
+    result.ok()
+}
+
+async fn run_all_tests(opts: &cli::Options, report: &Report) {
+    let command = opts.command();
+    let found_versions = if command == cli::Command::CheckReleases {
+        run_phase(
+            report,
+            "collect_versions",
+            cincinnati_graph_data::collect_versions(opts),
+        )
+        .await
+    } else {
+        run_phase(
+            report,
+            "verify_yaml",
+            cincinnati_graph_data::validate_yaml(opts, report),
+        )
+        .await
+    };
+    let found_versions = match found_versions {
+        Some(found_versions) => found_versions.all(),
+        None if opts.fail_fast || command == cli::Command::VerifyYaml => return,
+        // Check the registry anyway, unless the graph data couldn't even be parsed
+        None => match cincinnati_graph_data::collect_versions(opts).await {
+            Ok(found_versions) => found_versions.all(),
+            Err(_) => return,
+        },
+    };
+    report.count(Counter::VersionsFound, found_versions.len());
+    if command == cli::Command::VerifyYaml || !opts.is_selected(Check::RegistryPresence) {
+        return;
+    }
+    if opts.offline {
+        report.skip_phase("check_releases", OFFLINE_REASON);
+        return;
+    }
+    run_phase(
+        report,
+        "check_releases",
+        cincinnati_graph_data::check_releases(opts, &found_versions, report),
+    )
+    .await;
+}
+
+/// Print the versions the remaining phases would check, without touching the network.
+async fn list_versions(opts: &cli::Options, report: &Report) -> Fallible<()> {
+    let found_versions = cincinnati_graph_data::validate_yaml(opts, report).await?;
+    let mut all: Vec<Version> = found_versions.all().into_iter().collect();
+    all.sort();
+    for version in all.iter() {
+        println!("{}", version);
+    }
+    println!(
+        "{} versions: {} from channels, {} from blocked edges",
+        all.len(),
+        found_versions.channels.len(),
+        found_versions.blocked_edges.len()
+    );
     Ok(())
 }
 
+/// The phases `run` reports on for `opts`, besides those only run after a failure.
+fn planned_phases(opts: &cli::Options) -> Vec<&'static str> {
+    if opts.explain.is_some() {
+        vec!["explain"]
+    } else if opts.list_versions {
+        vec!["list_versions"]
+    } else if !opts.bench_concurrency.is_empty() {
+        vec!["bench"]
+    } else {
+        match opts.command() {
+            cli::Command::VerifyYaml => vec!["verify_yaml"],
+            cli::Command::CheckReleases => vec!["collect_versions", "check_releases"],
+            cli::Command::All => vec!["verify_yaml", "check_releases"],
+            cli::Command::Matrix => vec!["matrix"],
+        }
+    }
+}
+
+async fn run(opts: &cli::Options, report: &Report) -> RunReport {
+    if let Some(version) = &opts.explain {
+        run_phase(
+            report,
+            "explain",
+            cincinnati_graph_data::explain(opts, version),
+        )
+        .await;
+    } else if opts.list_versions {
+        run_phase(report, "list_versions", list_versions(opts, report)).await;
+    } else if !opts.bench_concurrency.is_empty() {
+        if opts.offline {
+            report.skip_phase("bench", OFFLINE_REASON);
+        } else {
+            run_phase(
+                report,
+                "bench",
+                cincinnati_graph_data::bench_scrape(opts, &opts.bench_concurrency),
+            )
+            .await;
+        }
+    } else if opts.command() == cli::Command::Matrix {
+        run_phase(report, "matrix", async {
+            print!("{}", cincinnati_graph_data::version_matrix(opts).await?);
+            Ok(())
+        })
+        .await;
+    } else {
+        run_all_tests(opts, report).await;
+    }
+    report.summary()
+}
+
+/// How a run ended, with the summary of what it recorded until then.
+#[derive(Debug)]
+enum Outcome {
+    Finished(RunReport),
+    /// Stopped at the `--deadline`, with the phases it didn't finish skipped.
+    Deadline(RunReport),
+    /// Stopped by Ctrl-C.
+    Interrupted(RunReport),
+}
+
+impl Outcome {
+    fn exit_code(&self, warnings_as_errors: bool) -> i32 {
+        match self {
+            Outcome::Finished(summary) => summary.exit_code(warnings_as_errors),
+            Outcome::Deadline(_) => EXIT_DEADLINE,
+            Outcome::Interrupted(_) => INTERRUPTED_EXIT_CODE,
+        }
+    }
+
+    fn into_summary(self) -> RunReport {
+        match self {
+            Outcome::Finished(summary)
+            | Outcome::Deadline(summary)
+            | Outcome::Interrupted(summary) => summary,
+        }
+    }
+}
+
+/// Race `run` against the `--deadline` and `interrupt`, which resolves on Ctrl-C.
+///
+/// Dropping the run when it loses cancels whatever it was awaiting.
+async fn run_until(
+    opts: &cli::Options,
+    report: &Report,
+    run: impl Future<Output = RunReport>,
+    interrupt: impl Future<Output = std::io::Result<()>>,
+) -> Outcome {
+    let deadline = tokio::time::delay_for(opts.deadline.unwrap_or_default());
+    tokio::select! {
+        summary = run => Outcome::Finished(summary),
+        _ = deadline, if opts.deadline.is_some() => {
+            let finished = report.summary();
+            for name in planned_phases(opts) {
+                if !finished.phases.iter().any(|phase| phase.name == name) {
+                    report.skip_phase(name, DEADLINE_REASON);
+                }
+            }
+            tracing::warn!("Stopped at the deadline, {} phase(s) completed", finished.phases.len());
+            Outcome::Deadline(report.summary())
+        }
+        Ok(()) = interrupt => Outcome::Interrupted(report.summary()),
+    }
+}
+
+/// Print every finding and the outcome of each phase, in color if stdout is a terminal and
+/// `NO_COLOR` is unset.
+fn print_summary(opts: &cli::Options, summary: &RunReport) {
+    let color = atty::is(atty::Stream::Stdout) && std::env::var_os("NO_COLOR").is_none();
+    print!("{}", summary.to_human(color, opts.verbose > 0));
+}
+
+/// Re-run the checks whenever a file in one of the graph data directories changes, until
+/// interrupted.
+///
+/// Only those directories are watched, so e.g. git operations elsewhere in the checkout don't
+/// trigger a run. Files created, removed or renamed during the watch are picked up by the next run.
+fn watch(opts: &cli::Options, runtime: &mut Runtime) -> Fallible<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::watcher(tx, WATCH_DEBOUNCE)?;
+    let dirs = cincinnati_graph_data::data_dirs(opts);
+    for dir in dirs.iter() {
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .context(format!("Watching {:?}", dir))?;
+    }
+    rerun_on_changes(&rx, || {
+        let report = Report::default();
+        runtime.block_on(async {
+            if opts.watch_all {
+                run_all_tests(opts, &report).await;
+            } else {
+                run_phase(
+                    &report,
+                    "verify_yaml",
+                    cincinnati_graph_data::validate_yaml(opts, &report),
+                )
+                .await;
+            }
+        });
+        print_summary(opts, &report.summary());
+        info!("Watching {:?} for changes", dirs);
+    });
+    Ok(())
+}
+
+/// Whether `event` is a change to re-run on.
+///
+/// The watcher sends a notice as soon as a file starts changing, then the change itself once it
+/// settled for `WATCH_DEBOUNCE`, so only the latter starts a run.
+fn is_change(event: &DebouncedEvent) -> bool {
+    match event {
+        DebouncedEvent::NoticeWrite(_) | DebouncedEvent::NoticeRemove(_) => false,
+        DebouncedEvent::Error(e, path) => {
+            tracing::warn!("Watch error for {:?}: {}", path, e);
+            false
+        }
+        _ => true,
+    }
+}
+
+/// Call `run`, then again after each change received from `events` until the watcher is gone.
+fn rerun_on_changes(events: &mpsc::Receiver<DebouncedEvent>, mut run: impl FnMut()) {
+    run();
+    for event in events.iter() {
+        if is_change(&event) {
+            run();
+        }
+    }
+}
+
+/// Log to stdout at the level selected by `-q` and `-v`, in the format selected by `--log-format`.
+fn init_logging(opts: &cli::Options) {
+    let level = match (opts.quiet, opts.verbose) {
+        (true, _) => Level::WARN,
+        (false, 0) => Level::INFO,
+        (false, 1) => Level::DEBUG,
+        (false, _) => Level::TRACE,
+    };
+    let subscriber = tracing_subscriber::fmt().with_max_level(level);
+    match opts.log_format {
+        cli::LogFormat::Human => subscriber.init(),
+        cli::LogFormat::Json => subscriber.json().init(),
+    }
+}
+
+/// Write `contents` to a temporary file next to `path` and rename it into place,
+/// so collectors never read a partially written file.
+fn write_atomically(path: &Path, contents: String) -> Fallible<()> {
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, contents).context(format!("Writing {:?}", tmp_path))?;
+    std::fs::rename(&tmp_path, path).context(format!("Renaming {:?} to {:?}", tmp_path, path))?;
+    Ok(())
+}
+
+/// Write each report requested with `--output`.
+fn write_outputs(opts: &cli::Options, report: &Report, summary: &RunReport) -> Fallible<()> {
+    // Findings refer to files by their resolved path
+    let data_dir = opts
+        .data_dir
+        .canonicalize()
+        .unwrap_or_else(|_| opts.data_dir.clone());
+    for output in opts.outputs.iter() {
+        let contents = match output.format {
+            cli::OutputFormat::Json => summary.to_json(),
+            cli::OutputFormat::Junit => report.to_junit(),
+            cli::OutputFormat::Sarif => summary.to_sarif(&data_dir),
+            cli::OutputFormat::Tap => summary.to_tap(),
+            cli::OutputFormat::GithubActions => summary.to_github_actions(&data_dir),
+            cli::OutputFormat::Markdown => summary.to_markdown(&data_dir),
+        };
+        match &output.path {
+            Some(path) => write_atomically(path, contents)?,
+            None => print!("{}", contents),
+        }
+    }
+    Ok(())
+}
+
+/// Push the metrics, comment on the pull request and notify of a `failed` run as configured,
+/// giving up on whatever is left once the `remaining` time until the deadline has passed.
+///
+/// The checks' outcome matters more than whether it could be posted, so failures are only logged.
+async fn publish(
+    opts: &cli::Options,
+    client: &reqwest::Client,
+    report: &Report,
+    summary: &RunReport,
+    failed: bool,
+    remaining: Option<Duration>,
+) {
+    let posts = async {
+        if let Some(url) = &opts.metrics_push_url {
+            if let Err(e) = cincinnati_graph_data::push_metrics(client, url, report).await {
+                tracing::warn!("{:#}", e);
+            }
+        }
+        if let Err(e) = cincinnati_graph_data::comment_on_pr(client, opts, summary).await {
+            tracing::warn!("Failed to comment on the pull request: {:#}", e);
+        }
+        if let Some(url) = opts.notify_url.as_ref().filter(|_| failed) {
+            if let Err(e) = cincinnati_graph_data::notify_failure(client, url, summary).await {
+                tracing::warn!("{:#}", e);
+            }
+        }
+    };
+    match remaining {
+        Some(remaining) => {
+            if tokio::time::timeout(remaining, posts).await.is_err() {
+                tracing::warn!("Stopped posting the results at the deadline");
+            }
+        }
+        None => posts.await,
+    }
+}
+
 fn main() -> Fallible<()> {
-    let mut runtime = tokio::runtime::Runtime::new()?;
-    runtime.block_on(run_all_tests())
+    let opts = cli::Options::load()?;
+    init_logging(&opts);
+    let report = Report::default();
+    let mut runtime = Runtime::new()?;
+    if opts.watch {
+        // Ctrl-C keeps its default behaviour here, terminating the watch immediately.
+        return watch(&opts, &mut runtime);
+    }
+    // Built first so a bad proxy or CA bundle fails before the checks rather than after them
+    let client = if opts.offline {
+        None
+    } else {
+        Some(cincinnati_graph_data::http_client(&opts)?)
+    };
+    let started = Instant::now();
+    let outcome = runtime.block_on(run_until(
+        &opts,
+        &report,
+        run(&opts, &report),
+        tokio::signal::ctrl_c(),
+    ));
+    if let Outcome::Interrupted(summary) = &outcome {
+        println!("Interrupted, {} phase(s) completed", summary.phases.len());
+        print_summary(&opts, summary);
+        std::process::exit(INTERRUPTED_EXIT_CODE);
+    }
+    let exit_code = outcome.exit_code(opts.warnings_as_errors);
+    let summary = outcome.into_summary();
+
+    if let Some(path) = &opts.metrics_out {
+        write_atomically(path, report.to_prometheus())?;
+    }
+    write_outputs(&opts, &report, &summary)?;
+    // A report on stdout is meant for another program, which the summary would confuse
+    if opts.outputs.iter().all(|output| output.path.is_some()) {
+        print_summary(&opts, &summary);
+    }
+    if let Some(client) = &client {
+        let remaining = opts
+            .deadline
+            .map(|deadline| deadline.checked_sub(started.elapsed()).unwrap_or_default());
+        runtime.block_on(publish(
+            &opts,
+            client,
+            &report,
+            &summary,
+            exit_code != 0,
+            remaining,
+        ));
+    }
+    std::process::exit(exit_code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cincinnati_graph_data::report::PhaseStatus;
+    use std::path::PathBuf;
+
+    /// A run which finishes verify_yaml, then hangs in the next phase.
+    async fn hanging_run(report: &Report) -> RunReport {
+        report.finish_phase("verify_yaml", Duration::default(), None);
+        tokio::time::delay_for(Duration::from_secs(3600)).await;
+        report.summary()
+    }
+
+    /// An interrupt which never comes.
+    async fn no_interrupt() -> std::io::Result<()> {
+        tokio::time::delay_for(Duration::from_secs(3600)).await;
+        Ok(())
+    }
+
+    #[test]
+    fn runs_stop_at_the_deadline() {
+        let opts = cli::Options {
+            deadline: Some(Duration::from_millis(50)),
+            ..cli::Options::default()
+        };
+        let report = Report::default();
+        let started = Instant::now();
+        let mut runtime = Runtime::new().unwrap();
+        let outcome = runtime.block_on(run_until(
+            &opts,
+            &report,
+            hanging_run(&report),
+            no_interrupt(),
+        ));
+
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(matches!(outcome, Outcome::Deadline(_)));
+        assert_eq!(outcome.exit_code(false), EXIT_DEADLINE);
+        let phases: Vec<_> = outcome
+            .into_summary()
+            .phases
+            .into_iter()
+            .map(|phase| (phase.name, phase.status, phase.error))
+            .collect();
+        assert_eq!(
+            phases,
+            vec![
+                ("verify_yaml", PhaseStatus::Passed, None),
+                (
+                    "check_releases",
+                    PhaseStatus::Skipped,
+                    Some(DEADLINE_REASON.to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_stop_when_interrupted() {
+        let opts = cli::Options::default();
+        let report = Report::default();
+        let started = Instant::now();
+        let mut runtime = Runtime::new().unwrap();
+        let outcome = runtime.block_on(run_until(&opts, &report, hanging_run(&report), async {
+            Ok(())
+        }));
+
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(matches!(outcome, Outcome::Interrupted(_)));
+        assert_eq!(outcome.exit_code(false), INTERRUPTED_EXIT_CODE);
+        assert_eq!(outcome.into_summary().phases.len(), 1);
+    }
+
+    #[test]
+    fn watch_reruns_once_each_file_change_settled() {
+        let (tx, rx) = mpsc::channel();
+        let path = PathBuf::from("channels/fast-4.11.yaml");
+        for event in vec![
+            DebouncedEvent::NoticeWrite(path.clone()),
+            DebouncedEvent::Write(path.clone()),
+            DebouncedEvent::Error(notify::Error::PathNotFound, Some(path.clone())),
+            DebouncedEvent::NoticeRemove(path.clone()),
+            DebouncedEvent::Remove(path),
+        ] {
+            tx.send(event).unwrap();
+        }
+        drop(tx);
+
+        let mut runs = 0;
+        rerun_on_changes(&rx, || runs += 1);
+        // The initial run, then one for the write and one for the removal
+        assert_eq!(runs, 3);
+    }
 }
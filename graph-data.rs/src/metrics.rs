@@ -0,0 +1,23 @@
+//! Pushing the run metrics to a Prometheus Pushgateway.
+
+use anyhow::{Context, Result as Fallible};
+use tracing::info;
+
+/// The job the metrics are grouped under on the Pushgateway.
+const JOB: &str = "cincinnati-graph-data";
+
+/// Replace the metrics of this job on the Pushgateway at `url` with `text`, in the Prometheus
+/// text format.
+pub async fn push(client: &reqwest::Client, url: &str, text: String) -> Fallible<()> {
+    let url = format!("{}/metrics/job/{}", url.trim_end_matches('/'), JOB);
+    client
+        .put(&url)
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .body(text)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .context(format!("Pushing metrics to {}", url))?;
+    info!("Pushed metrics to {}", url);
+    Ok(())
+}
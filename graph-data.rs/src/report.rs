@@ -0,0 +1,1170 @@
+//! Outcomes of individual checks, collected for machine-readable reports.
+
+use crate::checks;
+use ansi_term::{Colour, Style};
+use anyhow::Result as Fallible;
+use semver::Version;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The outcome of one check.
+#[derive(Clone, Debug)]
+pub struct TestCase {
+    /// The phase which ran the check.
+    pub suite: &'static str,
+    pub name: String,
+    /// Why the check failed, `None` if it passed.
+    pub failure: Option<String>,
+    /// Why the check didn't run, `None` if it did.
+    pub skipped: Option<String>,
+}
+
+/// How serious a finding is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Fails the check reporting it.
+    Error,
+    /// Only fails the check under `--strict`, and the run under `--warnings-as-errors`.
+    Warning,
+    /// Never fails anything.
+    Info,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        }
+    }
+}
+
+/// What a check found, with the file, line and version it is about where they are known.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Problem {
+    pub message: String,
+    pub file: Option<PathBuf>,
+    pub line: Option<usize>,
+    pub version: Option<Version>,
+}
+
+impl Problem {
+    pub fn new(message: impl Into<String>) -> Self {
+        Problem {
+            message: message.into(),
+            ..Problem::default()
+        }
+    }
+
+    /// The problem is in `file`.
+    pub fn in_file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// The problem is on `line` of its file, counting from 1.
+    pub fn at_line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    /// The problem is about `version`.
+    pub fn about(mut self, version: &Version) -> Self {
+        self.version = Some(version.clone());
+        self
+    }
+}
+
+impl From<String> for Problem {
+    fn from(message: String) -> Self {
+        Problem::new(message)
+    }
+}
+
+impl From<&str> for Problem {
+    fn from(message: &str) -> Self {
+        Problem::new(message)
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// One problem or observation reported by a check.
+#[derive(Clone, Debug)]
+pub struct Finding {
+    pub suite: &'static str,
+    /// The check reporting the finding.
+    pub check: String,
+    pub severity: Severity,
+    pub message: String,
+    /// The file the finding is about, if any.
+    pub file: Option<PathBuf>,
+    /// The line of `file` the finding is about, if known.
+    pub line: Option<usize>,
+    /// The version the finding is about, if any.
+    pub version: Option<Version>,
+}
+
+impl Finding {
+    fn new(suite: &'static str, check: String, severity: Severity, problem: Problem) -> Self {
+        Finding {
+            suite,
+            check,
+            severity,
+            message: problem.message,
+            file: problem.file,
+            line: problem.line,
+            version: problem.version,
+        }
+    }
+}
+
+/// The outcome and duration of one named check, for the end-of-run summary.
+#[derive(Clone, Debug)]
+pub struct CheckSummary {
+    pub suite: &'static str,
+    pub name: String,
+    pub errors: usize,
+    pub warnings: usize,
+    pub duration: Duration,
+}
+
+impl CheckSummary {
+    /// `fail` if the check reported errors, `warn` if it only reported warnings, `pass` otherwise.
+    pub fn status(&self) -> &'static str {
+        if self.errors > 0 {
+            "fail"
+        } else if self.warnings > 0 {
+            "warn"
+        } else {
+            "pass"
+        }
+    }
+}
+
+/// Quantities counted during a run and exposed as metrics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Counter {
+    FilesValidated,
+    VersionsFound,
+    ReleasesScraped,
+    VersionsMissing,
+    RegistryErrors,
+}
+
+impl Counter {
+    fn name(self) -> &'static str {
+        match self {
+            Counter::FilesValidated => "graph_data_files_validated",
+            Counter::VersionsFound => "graph_data_versions_found",
+            Counter::ReleasesScraped => "graph_data_releases_scraped",
+            Counter::VersionsMissing => "graph_data_versions_missing",
+            Counter::RegistryErrors => "graph_data_registry_errors",
+        }
+    }
+
+    fn help(self) -> &'static str {
+        match self {
+            Counter::FilesValidated => "Number of graph data files deserialized successfully.",
+            Counter::VersionsFound => "Number of distinct versions mentioned in the graph data.",
+            Counter::ReleasesScraped => {
+                "Number of distinct release versions found in the registry."
+            }
+            Counter::VersionsMissing => "Number of mentioned versions missing from the registry.",
+            Counter::RegistryErrors => "Number of failed registry scrapes, including retried ones.",
+        }
+    }
+}
+
+/// Whether a phase ran to completion.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhaseStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+impl PhaseStatus {
+    pub fn name(self) -> &'static str {
+        match self {
+            PhaseStatus::Passed => "passed",
+            PhaseStatus::Failed => "failed",
+            PhaseStatus::Skipped => "skipped",
+        }
+    }
+}
+
+/// The outcome of one phase of a run.
+#[derive(Clone, Debug)]
+pub struct PhaseReport {
+    pub name: &'static str,
+    pub status: PhaseStatus,
+    pub duration: Duration,
+    /// The error which failed the phase, including its causes, or why it was skipped.
+    pub error: Option<String>,
+}
+
+/// A snapshot of everything recorded during a run, for rendering and choosing an exit code.
+#[derive(Clone, Debug, Default)]
+pub struct RunReport {
+    pub phases: Vec<PhaseReport>,
+    pub counters: BTreeMap<Counter, u64>,
+    /// `(suite, name, failure)` of every failed check, in the order they were recorded.
+    pub failures: Vec<(&'static str, String, String)>,
+    pub findings: Vec<Finding>,
+    /// Every timed check, in the order they finished.
+    pub checks: Vec<CheckSummary>,
+}
+
+/// Exit code of a run failing for a reason without a code of its own.
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit code of a run which found problems in the graph data.
+pub const EXIT_INVALID_DATA: i32 = 2;
+/// Exit code of a run which found versions missing from the release registry.
+pub const EXIT_MISSING_RELEASE: i32 = 3;
+/// Exit code of a run stopped by `--deadline` before every phase finished.
+pub const EXIT_DEADLINE: i32 = 4;
+/// Exit code of a run which couldn't scrape the release registry; retrying may help.
+pub const EXIT_INFRASTRUCTURE: i32 = 5;
+
+impl RunReport {
+    /// Zero if every phase passed, otherwise the exit code for the first phase which failed.
+    ///
+    /// With `warnings_as_errors`, any warning fails the run with `EXIT_INVALID_DATA` too.
+    pub fn exit_code(&self, warnings_as_errors: bool) -> i32 {
+        let scrape_failed = self
+            .failures
+            .iter()
+            .any(|(suite, name, _)| *suite == "check_releases" && name == "scrape");
+        let failed = self
+            .phases
+            .iter()
+            .find(|phase| phase.status == PhaseStatus::Failed);
+        if let Some(phase) = failed {
+            return match phase.name {
+                "verify_yaml" | "collect_versions" => EXIT_INVALID_DATA,
+                "check_releases" if scrape_failed => EXIT_INFRASTRUCTURE,
+                "check_releases" => EXIT_MISSING_RELEASE,
+                _ => EXIT_FAILURE,
+            };
+        }
+        let warned = self
+            .findings
+            .iter()
+            .any(|finding| finding.severity == Severity::Warning);
+        if warnings_as_errors && warned {
+            EXIT_INVALID_DATA
+        } else {
+            0
+        }
+    }
+
+    /// Render every finding with the duration of the check reporting it, along with the checks,
+    /// phases and counters, as a JSON document.
+    pub fn to_json(&self) -> String {
+        let duration = |finding: &Finding| {
+            self.checks
+                .iter()
+                .find(|check| check.suite == finding.suite && check.name == finding.check)
+                .map(|check| check.duration.as_secs_f64())
+        };
+        let findings: Vec<serde_json::Value> = self
+            .findings
+            .iter()
+            .map(|finding| {
+                json!({
+                    "suite": finding.suite,
+                    "check": finding.check,
+                    "severity": finding.severity.name(),
+                    "version": finding.version.as_ref().map(ToString::to_string),
+                    "file": finding.file,
+                    "line": finding.line,
+                    "message": finding.message,
+                    "duration_seconds": duration(finding),
+                })
+            })
+            .collect();
+        let checks: Vec<serde_json::Value> = self
+            .checks
+            .iter()
+            .map(|check| {
+                json!({
+                    "suite": check.suite,
+                    "check": check.name,
+                    "status": check.status(),
+                    "errors": check.errors,
+                    "warnings": check.warnings,
+                    "duration_seconds": check.duration.as_secs_f64(),
+                })
+            })
+            .collect();
+        let phases: Vec<serde_json::Value> = self
+            .phases
+            .iter()
+            .map(|phase| {
+                json!({
+                    "phase": phase.name,
+                    "status": phase.status.name(),
+                    "duration_seconds": phase.duration.as_secs_f64(),
+                    "error": phase.error,
+                })
+            })
+            .collect();
+        let counters: serde_json::Map<String, serde_json::Value> = self
+            .counters
+            .iter()
+            .map(|(counter, value)| (counter.name().to_string(), json!(value)))
+            .collect();
+        let document = json!({
+            "findings": findings,
+            "checks": checks,
+            "phases": phases,
+            "counters": counters,
+        });
+        format!("{:#}\n", document)
+    }
+
+    /// Render every check as a TAP line, followed by the phases which were skipped or failed
+    /// without a failing check.
+    ///
+    /// The findings of a failed check are listed in its YAML diagnostic block.
+    pub fn to_tap(&self) -> String {
+        // (passed, description, YAML diagnostic lines) of every test point
+        let mut points: Vec<(bool, String, Vec<String>)> = vec![];
+        for check in self.checks.iter() {
+            let mut description = format!("{}/{}", check.suite, check.name);
+            if check.warnings > 0 {
+                description.push_str(&format!(" # {} warning(s)", check.warnings));
+            }
+            let mut diagnostic = vec![];
+            if check.status() == "fail" {
+                diagnostic.push("messages:".to_string());
+                let errors = self.findings.iter().filter(|finding| {
+                    finding.suite == check.suite
+                        && finding.check == check.name
+                        && finding.severity == Severity::Error
+                });
+                for finding in errors {
+                    diagnostic.push(format!("  - {:?}", finding.message));
+                }
+            }
+            points.push((check.status() != "fail", description, diagnostic));
+        }
+        for phase in self.phases.iter() {
+            let reason = phase.error.as_deref().unwrap_or_default();
+            let check_failed = self
+                .checks
+                .iter()
+                .any(|check| check.suite == phase.name && check.status() == "fail");
+            match phase.status {
+                PhaseStatus::Skipped => {
+                    points.push((true, format!("{} # SKIP {}", phase.name, reason), vec![]))
+                }
+                PhaseStatus::Failed if !check_failed => points.push((
+                    false,
+                    phase.name.to_string(),
+                    vec![format!("message: {:?}", reason)],
+                )),
+                _ => {}
+            }
+        }
+
+        let mut text = format!("TAP version 13\n1..{}\n", points.len());
+        for (i, (passed, description, diagnostic)) in points.iter().enumerate() {
+            let status = if *passed { "ok" } else { "not ok" };
+            text.push_str(&format!("{} {} - {}\n", status, i + 1, description));
+            if !diagnostic.is_empty() {
+                text.push_str("  ---\n");
+                for line in diagnostic.iter() {
+                    text.push_str(&format!("  {}\n", line));
+                }
+                text.push_str("  ...\n");
+            }
+        }
+        text
+    }
+
+    /// Render every finding as a GitHub Actions workflow command like `::error file=F,line=N::`,
+    /// which annotates the line on the pull request, with file paths relative to `root`.
+    pub fn to_github_actions(&self, root: &Path) -> String {
+        let mut text = String::new();
+        for finding in self.findings.iter() {
+            let command = match finding.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Info => "notice",
+            };
+            let mut properties = vec![format!("title={}", escape_property(&finding.check))];
+            if let Some(file) = &finding.file {
+                let file = file.strip_prefix(root).unwrap_or(file);
+                properties.push(format!("file={}", escape_property(&file.to_string_lossy())));
+                if let Some(line) = finding.line {
+                    properties.push(format!("line={}", line));
+                }
+            }
+            text.push_str(&format!(
+                "::{} {}::{}\n",
+                command,
+                properties.join(","),
+                escape_data(&finding.message)
+            ));
+        }
+        text
+    }
+
+    /// Render a Markdown report: a table of the checks, then each check's errors and warnings with
+    /// links to the offending files relative to `root`, then the phases.
+    pub fn to_markdown(&self, root: &Path) -> String {
+        let cell = |s: &str| s.replace('|', "\\|").replace('\n', "<br>");
+        let mut text = String::from("# Graph data checks\n\n");
+        text.push_str("| check | status | errors | warnings | duration |\n|---|---|---|---|---|\n");
+        for check in self.checks.iter() {
+            text.push_str(&format!(
+                "| {} | {} | {} | {} | {:.1}s |\n",
+                check.name,
+                check.status(),
+                check.errors,
+                check.warnings,
+                check.duration.as_secs_f64()
+            ));
+        }
+        for check in self.checks.iter().filter(|check| check.status() != "pass") {
+            text.push_str(&format!(
+                "\n## {}\n\n| severity | version | file | message |\n|---|---|---|---|\n",
+                check.name
+            ));
+            let findings = self.findings.iter().filter(|finding| {
+                finding.suite == check.suite
+                    && finding.check == check.name
+                    && finding.severity != Severity::Info
+            });
+            for finding in findings {
+                let file = match &finding.file {
+                    Some(file) => {
+                        let file = file.strip_prefix(root).unwrap_or(file).to_string_lossy();
+                        match finding.line {
+                            Some(line) => format!("[{}:{}]({}#L{})", file, line, file, line),
+                            None => format!("[{}]({})", file, file),
+                        }
+                    }
+                    None => String::new(),
+                };
+                text.push_str(&format!(
+                    "| {} | {} | {} | {} |\n",
+                    finding.severity.name(),
+                    finding
+                        .version
+                        .as_ref()
+                        .map(ToString::to_string)
+                        .unwrap_or_default(),
+                    file,
+                    cell(&finding.message)
+                ));
+            }
+        }
+        text.push_str("\n## Phases\n\n");
+        for phase in self.phases.iter() {
+            text.push_str(&format!(
+                "- {} {} in {:.1}s\n",
+                phase.name,
+                phase.status.name(),
+                phase.duration.as_secs_f64()
+            ));
+            if let Some(error) = &phase.error {
+                text.push_str(&format!(
+                    "\n  ```\n  {}\n  ```\n",
+                    error.replace('\n', "\n  ")
+                ));
+            }
+        }
+        text
+    }
+
+    /// Render every finding as a SARIF log, for GitHub code scanning to annotate the offending
+    /// lines, with file paths relative to `root`.
+    pub fn to_sarif(&self, root: &Path) -> String {
+        let mut rules: Vec<&str> = self.findings.iter().map(|f| f.check.as_str()).collect();
+        rules.sort();
+        rules.dedup();
+        let rules: Vec<serde_json::Value> = rules
+            .into_iter()
+            .map(|name| {
+                let description = checks::find(name).map_or(name, |check| check.description);
+                json!({ "id": name, "shortDescription": { "text": description } })
+            })
+            .collect();
+        let results: Vec<serde_json::Value> = self
+            .findings
+            .iter()
+            .map(|finding| {
+                let level = match finding.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                    Severity::Info => "note",
+                };
+                let locations: Vec<serde_json::Value> = finding
+                    .file
+                    .iter()
+                    .map(|file| {
+                        let uri = file.strip_prefix(root).unwrap_or(file);
+                        let mut location = json!({
+                            "artifactLocation": { "uri": uri.to_string_lossy() },
+                        });
+                        if let Some(line) = finding.line {
+                            location["region"] = json!({ "startLine": line });
+                        }
+                        json!({ "physicalLocation": location })
+                    })
+                    .collect();
+                json!({
+                    "ruleId": finding.check,
+                    "level": level,
+                    "message": { "text": finding.message },
+                    "locations": locations,
+                })
+            })
+            .collect();
+        let log = json!({
+            "version": "2.1.0",
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "runs": [{
+                "tool": { "driver": { "name": env!("CARGO_PKG_NAME"), "rules": rules } },
+                "results": results,
+            }],
+        });
+        format!("{:#}\n", log)
+    }
+
+    /// Render one line per finding and per phase for a terminal, highlighting what each finding
+    /// is about, i.e. the text before its first `: ` or else its first word.
+    ///
+    /// Failed phases show the first line of their error, or the whole chain if `verbose`.
+    pub fn to_human(&self, color: bool, verbose: bool) -> String {
+        let paint = |style: Style, s: &str| {
+            if color {
+                style.paint(s).to_string()
+            } else {
+                s.to_string()
+            }
+        };
+
+        let mut text = String::new();
+        for finding in self.findings.iter() {
+            let (label, style) = match finding.severity {
+                Severity::Error => ("error", Colour::Red.bold()),
+                Severity::Warning => ("warning", Colour::Yellow.bold()),
+                Severity::Info => ("info", Colour::Blue.normal()),
+            };
+            let split = finding
+                .message
+                .find(": ")
+                .or_else(|| finding.message.find(' '))
+                .unwrap_or_else(|| finding.message.len());
+            let (subject, rest) = finding.message.split_at(split);
+            text.push_str(&format!(
+                "{}[{}] {}{}\n",
+                paint(style, label),
+                finding.check,
+                paint(Style::new().bold(), subject),
+                rest
+            ));
+        }
+        if !self.checks.is_empty() {
+            text.push_str(&format!(
+                "{:<24} {:<6} {:>8} {:>8} {:>10}\n",
+                "check", "status", "errors", "warnings", "duration"
+            ));
+            for check in self.checks.iter() {
+                let status = match check.status() {
+                    "fail" => paint(Colour::Red.bold(), "fail  "),
+                    "warn" => paint(Colour::Yellow.bold(), "warn  "),
+                    _ => paint(Colour::Green.normal(), "pass  "),
+                };
+                text.push_str(&format!(
+                    "{:<24} {} {:>8} {:>8} {:>9.1}s\n",
+                    check.name,
+                    status,
+                    check.errors,
+                    check.warnings,
+                    check.duration.as_secs_f64()
+                ));
+            }
+            let with_status =
+                |status: &str| self.checks.iter().filter(|c| c.status() == status).count();
+            text.push_str(&format!(
+                "{} checks: {} passed, {} failed, {} warned\n",
+                self.checks.len(),
+                with_status("pass"),
+                with_status("fail"),
+                with_status("warn")
+            ));
+        }
+        for phase in self.phases.iter() {
+            let status = match phase.status {
+                PhaseStatus::Passed => paint(Colour::Green.normal(), "passed"),
+                PhaseStatus::Failed => paint(Colour::Red.bold(), "failed"),
+                PhaseStatus::Skipped => paint(Colour::Yellow.normal(), "SKIPPED"),
+            };
+            text.push_str(&format!(
+                "{} {} in {:.1}s\n",
+                phase.name,
+                status,
+                phase.duration.as_secs_f64()
+            ));
+            if let Some(error) = &phase.error {
+                let error = if verbose {
+                    error.as_str()
+                } else {
+                    error.lines().next().unwrap_or_default()
+                };
+                for line in error.lines() {
+                    text.push_str(&format!("  {}\n", line));
+                }
+            }
+        }
+        text
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    cases: Mutex<Vec<TestCase>>,
+    counters: Mutex<BTreeMap<Counter, u64>>,
+    phases: Mutex<Vec<PhaseReport>>,
+    findings: Mutex<Vec<Finding>>,
+    timings: Mutex<Vec<(&'static str, String, Duration)>>,
+}
+
+impl Report {
+    pub fn record(&self, suite: &'static str, name: impl Into<String>, failure: Option<String>) {
+        self.cases.lock().unwrap().push(TestCase {
+            suite,
+            name: name.into(),
+            failure,
+            skipped: None,
+        });
+    }
+
+    /// Record that the phase `name` didn't run, and why.
+    pub fn skip_phase(&self, name: &'static str, reason: &str) {
+        self.cases.lock().unwrap().push(TestCase {
+            suite: name,
+            name: name.to_string(),
+            failure: None,
+            skipped: Some(reason.to_string()),
+        });
+        self.phases.lock().unwrap().push(PhaseReport {
+            name,
+            status: PhaseStatus::Skipped,
+            duration: Duration::default(),
+            error: Some(reason.to_string()),
+        });
+    }
+
+    /// Record whether `result` is a failure and pass it through.
+    pub fn record_result<T>(
+        &self,
+        suite: &'static str,
+        name: impl Into<String>,
+        result: Fallible<T>,
+    ) -> Fallible<T> {
+        let failure = result.as_ref().err().map(|e| format!("{:#}", e));
+        self.record(suite, name, failure);
+        result
+    }
+
+    /// Record a finding of `severity` by the check `check`.
+    pub fn finding(
+        &self,
+        suite: &'static str,
+        check: impl Into<String>,
+        severity: Severity,
+        problem: impl Into<Problem>,
+    ) {
+        let finding = Finding::new(suite, check.into(), severity, problem.into());
+        self.findings.lock().unwrap().push(finding);
+    }
+
+    /// Record how long the check `name` took.
+    pub fn time_check(&self, suite: &'static str, name: impl Into<String>, duration: Duration) {
+        self.timings
+            .lock()
+            .unwrap()
+            .push((suite, name.into(), duration));
+    }
+
+    /// Add `n` to `counter`.
+    pub fn count(&self, counter: Counter, n: usize) {
+        *self.counters.lock().unwrap().entry(counter).or_default() += n as u64;
+    }
+
+    /// Record how long `phase` took to run and the error which failed it, if any.
+    pub fn finish_phase(
+        &self,
+        name: &'static str,
+        duration: Duration,
+        error: Option<&anyhow::Error>,
+    ) {
+        self.phases.lock().unwrap().push(PhaseReport {
+            name,
+            status: match error {
+                None => PhaseStatus::Passed,
+                Some(_) => PhaseStatus::Failed,
+            },
+            duration,
+            error: error.map(|e| format!("{:?}", e)),
+        });
+    }
+
+    /// Take a snapshot of the phases, counters and failed checks recorded so far.
+    pub fn summary(&self) -> RunReport {
+        let findings = self.findings.lock().unwrap();
+        let count = |suite: &str, name: &str, severity: Severity| {
+            findings
+                .iter()
+                .filter(|f| f.suite == suite && f.check == name && f.severity == severity)
+                .count()
+        };
+        let checks = self
+            .timings
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(suite, name, duration)| CheckSummary {
+                suite: *suite,
+                name: name.clone(),
+                errors: count(*suite, name.as_str(), Severity::Error),
+                warnings: count(*suite, name.as_str(), Severity::Warning),
+                duration: *duration,
+            })
+            .collect();
+        RunReport {
+            phases: self.phases.lock().unwrap().clone(),
+            counters: self.counters.lock().unwrap().clone(),
+            failures: self
+                .cases
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|case| {
+                    case.failure
+                        .as_ref()
+                        .map(|failure| (case.suite, case.name.clone(), failure.clone()))
+                })
+                .collect(),
+            findings: findings.clone(),
+            checks,
+        }
+    }
+
+    /// Render counters, check outcomes and phase durations in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+        for (counter, value) in self.counters.lock().unwrap().iter() {
+            text.push_str(&format!(
+                "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}\n",
+                name = counter.name(),
+                help = counter.help(),
+                value = value
+            ));
+        }
+
+        let cases = self.cases.lock().unwrap();
+        let failed = cases.iter().filter(|c| c.failure.is_some()).count();
+        let skipped = cases.iter().filter(|c| c.skipped.is_some()).count();
+        text.push_str("# HELP graph_data_checks Number of checks run, by result.\n");
+        text.push_str("# TYPE graph_data_checks gauge\n");
+        text.push_str(&format!(
+            "graph_data_checks{{result=\"passed\"}} {}\n",
+            cases.len() - failed - skipped
+        ));
+        text.push_str(&format!(
+            "graph_data_checks{{result=\"failed\"}} {}\n",
+            failed
+        ));
+        text.push_str(&format!(
+            "graph_data_checks{{result=\"skipped\"}} {}\n",
+            skipped
+        ));
+
+        text.push_str("# HELP graph_data_check_duration_seconds Time spent in each check.\n");
+        text.push_str("# TYPE graph_data_check_duration_seconds gauge\n");
+        for (suite, name, duration) in self.timings.lock().unwrap().iter() {
+            text.push_str(&format!(
+                "graph_data_check_duration_seconds{{suite=\"{}\",check=\"{}\"}} {}\n",
+                suite,
+                name,
+                duration.as_secs_f64()
+            ));
+        }
+
+        text.push_str("# HELP graph_data_phase_duration_seconds Time spent in each phase.\n");
+        text.push_str("# TYPE graph_data_phase_duration_seconds gauge\n");
+        for phase in self.phases.lock().unwrap().iter() {
+            if phase.status == PhaseStatus::Skipped {
+                continue;
+            }
+            text.push_str(&format!(
+                "graph_data_phase_duration_seconds{{phase=\"{}\"}} {}\n",
+                phase.name,
+                phase.duration.as_secs_f64()
+            ));
+        }
+        text
+    }
+
+    /// One case per check and version the check reported errors for, or a single passing case
+    /// for a check without any.
+    ///
+    /// The registry checks record a case per version themselves, so they are left out.
+    ///
+    /// These replace the single case the YAML checks record for themselves.
+    fn check_cases(&self) -> Vec<TestCase> {
+        let findings = self.findings.lock().unwrap();
+        let mut cases = vec![];
+        for (suite, check, _) in self.timings.lock().unwrap().iter() {
+            if *suite == "check_releases" {
+                continue;
+            }
+            let mut failures: Vec<(Option<&Version>, Vec<&str>)> = vec![];
+            let errors = findings.iter().filter(|finding| {
+                finding.suite == *suite
+                    && finding.check == *check
+                    && finding.severity == Severity::Error
+            });
+            for finding in errors {
+                let version = finding.version.as_ref();
+                match failures.iter_mut().find(|(v, _)| *v == version) {
+                    Some((_, messages)) => messages.push(&finding.message),
+                    None => failures.push((version, vec![&finding.message])),
+                }
+            }
+            if failures.is_empty() {
+                cases.push(TestCase {
+                    suite,
+                    name: check.clone(),
+                    failure: None,
+                    skipped: None,
+                });
+            }
+            for (version, messages) in failures {
+                cases.push(TestCase {
+                    suite,
+                    name: match version {
+                        Some(version) => format!("{} {}", check, version),
+                        None => check.clone(),
+                    },
+                    failure: Some(messages.join("\n")),
+                    skipped: None,
+                });
+            }
+        }
+        cases
+    }
+
+    /// Render all recorded cases and a case per check and version as a JUnit XML document, one
+    /// `<testsuite>` per phase.
+    pub fn to_junit(&self) -> String {
+        let check_cases = self.check_cases();
+        let timings = self.timings.lock().unwrap();
+        let mut cases: Vec<TestCase> = self
+            .cases
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|case| {
+                case.suite == "check_releases"
+                    || !timings
+                        .iter()
+                        .any(|(suite, name, _)| *suite == case.suite && *name == case.name)
+            })
+            .cloned()
+            .collect();
+        cases.extend(check_cases);
+        let mut suites: Vec<(&str, Vec<&TestCase>)> = vec![];
+        for case in cases.iter() {
+            match suites.iter_mut().find(|(suite, _)| *suite == case.suite) {
+                Some((_, suite_cases)) => suite_cases.push(case),
+                None => suites.push((case.suite, vec![case])),
+            }
+        }
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+        for (suite, suite_cases) in suites.iter() {
+            let failures = suite_cases.iter().filter(|c| c.failure.is_some()).count();
+            let skipped = suite_cases.iter().filter(|c| c.skipped.is_some()).count();
+            xml.push_str(&format!(
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\">\n",
+                escape(suite),
+                suite_cases.len(),
+                failures,
+                skipped
+            ));
+            for case in suite_cases.iter() {
+                let attributes = format!(
+                    "classname=\"{}\" name=\"{}\"",
+                    escape(case.suite),
+                    escape(&case.name)
+                );
+                match (&case.failure, &case.skipped) {
+                    (None, None) => xml.push_str(&format!("    <testcase {}/>\n", attributes)),
+                    (None, Some(reason)) => xml.push_str(&format!(
+                        "    <testcase {}>\n      <skipped message=\"{}\"/>\n    </testcase>\n",
+                        attributes,
+                        escape(reason)
+                    )),
+                    (Some(failure), _) => xml.push_str(&format!(
+                        "    <testcase {}>\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+                        attributes,
+                        escape(failure.lines().next().unwrap_or_default()),
+                        escape(failure)
+                    )),
+                }
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+}
+
+/// Escape the message of a GitHub Actions workflow command.
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a property value of a GitHub Actions workflow command.
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEAD_END: &str =
+        "\"/data/channels/fast-4.5.yaml\": every update from 4.5.1 within fast-4.5 is blocked";
+
+    /// A run whose YAML phase failed on a dead end and warned about a version, and which skipped
+    /// the registry.
+    fn sample() -> Report {
+        let report = Report::default();
+        report.finding(
+            "verify_yaml",
+            "dead-ends",
+            Severity::Error,
+            Problem::new(DEAD_END)
+                .in_file("/data/channels/fast-4.5.yaml")
+                .at_line(3)
+                .about(&Version::parse("4.5.1").unwrap()),
+        );
+        report.finding(
+            "verify_yaml",
+            "version-bounds",
+            Severity::Warning,
+            "4.5.1000: patch 1000 is larger than 999",
+        );
+        report
+            .record_result::<()>(
+                "verify_yaml",
+                "dead-ends",
+                Err(anyhow::anyhow!(
+                    "Found versions left without an update:\n{}",
+                    DEAD_END
+                )),
+            )
+            .unwrap_err();
+        report.time_check("verify_yaml", "dead-ends", Duration::from_millis(1500));
+        report.record("verify_yaml", "version-bounds", None);
+        report.time_check("verify_yaml", "version-bounds", Duration::from_millis(100));
+        report.count(Counter::FilesValidated, 3);
+        report.finish_phase(
+            "verify_yaml",
+            Duration::from_secs(2),
+            Some(&anyhow::anyhow!(
+                "Found versions left without an update\n{}",
+                DEAD_END
+            )),
+        );
+        report.skip_phase("check_releases", "--offline");
+        report
+    }
+
+    fn root() -> &'static Path {
+        Path::new("/data")
+    }
+
+    #[test]
+    fn exit_code_follows_the_first_failed_phase() {
+        assert_eq!(sample().summary().exit_code(false), EXIT_INVALID_DATA);
+
+        let report = Report::default();
+        report.finish_phase("verify_yaml", Duration::default(), None);
+        let error = anyhow::anyhow!("Found missing releases");
+        report.finish_phase("check_releases", Duration::default(), Some(&error));
+        assert_eq!(report.summary().exit_code(false), EXIT_MISSING_RELEASE);
+
+        report.record(
+            "check_releases",
+            "scrape",
+            Some("connection refused".to_string()),
+        );
+        assert_eq!(report.summary().exit_code(false), EXIT_INFRASTRUCTURE);
+
+        let report = Report::default();
+        report.finish_phase("watch", Duration::default(), Some(&error));
+        assert_eq!(report.summary().exit_code(false), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn exit_code_fails_on_warnings_if_asked_to() {
+        let report = Report::default();
+        report.finding(
+            "verify_yaml",
+            "version-bounds",
+            Severity::Warning,
+            "4.5.1000",
+        );
+        report.finish_phase("verify_yaml", Duration::default(), None);
+        assert_eq!(report.summary().exit_code(false), 0);
+        assert_eq!(report.summary().exit_code(true), EXIT_INVALID_DATA);
+    }
+
+    #[test]
+    fn json_lists_findings_checks_phases_and_counters() {
+        let document: serde_json::Value =
+            serde_json::from_str(&sample().summary().to_json()).unwrap();
+        assert_eq!(document["findings"][0]["check"], "dead-ends");
+        assert_eq!(document["findings"][0]["version"], "4.5.1");
+        assert_eq!(document["findings"][0]["line"], 3);
+        assert_eq!(document["findings"][0]["duration_seconds"], 1.5);
+        assert_eq!(document["findings"][1]["file"], serde_json::Value::Null);
+        assert_eq!(document["checks"][0]["status"], "fail");
+        assert_eq!(document["checks"][1]["status"], "warn");
+        assert_eq!(document["phases"][1]["status"], "skipped");
+        assert_eq!(document["counters"]["graph_data_files_validated"], 3);
+    }
+
+    #[test]
+    fn tap_has_a_test_point_per_check_and_skipped_phase() {
+        let tap = sample().summary().to_tap();
+        assert!(tap.starts_with("TAP version 13\n1..3\n"), "{}", tap);
+        assert!(tap.contains(&format!(
+            "not ok 1 - verify_yaml/dead-ends\n  ---\n  messages:\n    - {:?}\n  ...\n",
+            DEAD_END
+        )));
+        assert!(tap.contains("ok 2 - verify_yaml/version-bounds # 1 warning(s)\n"));
+        assert!(tap.ends_with("ok 3 - check_releases # SKIP --offline\n"));
+    }
+
+    #[test]
+    fn github_actions_annotate_relative_files() {
+        let text = sample().summary().to_github_actions(root());
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                format!(
+                    "::error title=dead-ends,file=channels/fast-4.5.yaml,line=3::{}",
+                    DEAD_END
+                ),
+                "::warning title=version-bounds::4.5.1000: patch 1000 is larger than 999"
+                    .to_string(),
+            ]
+        );
+        assert_eq!(escape_data("50%\nof"), "50%25%0Aof");
+        assert_eq!(escape_property("a:b,c"), "a%3Ab%2Cc");
+    }
+
+    #[test]
+    fn markdown_links_findings_to_their_lines() {
+        let text = sample().summary().to_markdown(root());
+        assert!(text.contains("| dead-ends | fail | 1 | 0 | 1.5s |\n"));
+        assert!(text
+            .contains("| error | 4.5.1 | [channels/fast-4.5.yaml:3](channels/fast-4.5.yaml#L3) |"));
+        assert!(text.contains("## version-bounds\n"));
+        assert!(text.contains("- check_releases skipped in 0.0s\n"));
+    }
+
+    #[test]
+    fn sarif_locates_results_and_describes_rules() {
+        let log: serde_json::Value =
+            serde_json::from_str(&sample().summary().to_sarif(root())).unwrap();
+        let run = &log["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "dead-ends");
+        assert_eq!(
+            run["tool"]["driver"]["rules"][0]["shortDescription"]["text"],
+            checks::find("dead-ends").unwrap().description
+        );
+        let location = &run["results"][0]["locations"][0]["physicalLocation"];
+        assert_eq!(
+            location["artifactLocation"]["uri"],
+            "channels/fast-4.5.yaml"
+        );
+        assert_eq!(location["region"]["startLine"], 3);
+        assert_eq!(run["results"][1]["level"], "warning");
+        assert_eq!(run["results"][1]["locations"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn human_summarizes_checks_and_phases() {
+        let summary = sample().summary();
+        let text = summary.to_human(false, false);
+        assert!(text.starts_with(&format!("error[dead-ends] {}\n", DEAD_END)));
+        assert!(text.contains("2 checks: 0 passed, 1 failed, 1 warned\n"));
+        assert!(
+            text.contains("verify_yaml failed in 2.0s\n  Found versions left without an update\n")
+        );
+        assert!(!text.contains(&format!("  {}\n", DEAD_END)));
+        assert!(summary
+            .to_human(false, true)
+            .contains(&format!("  {}\n", DEAD_END)));
+    }
+
+    #[test]
+    fn junit_has_a_case_per_failed_version() {
+        let xml = sample().to_junit();
+        assert!(xml.contains(
+            "<testcase classname=\"verify_yaml\" name=\"dead-ends 4.5.1\">\n      <failure message=\"&quot;/data/channels/fast-4.5.yaml&quot;: every update"
+        ));
+        assert!(xml.contains("<testcase classname=\"verify_yaml\" name=\"version-bounds\"/>"));
+        assert!(xml
+            .contains("<testsuite name=\"verify_yaml\" tests=\"2\" failures=\"1\" skipped=\"0\">"));
+        assert!(!xml.contains("name=\"dead-ends\""));
+        assert!(xml.contains(
+            "<testsuite name=\"check_releases\" tests=\"1\" failures=\"0\" skipped=\"1\">"
+        ));
+        assert!(xml.contains("<skipped message=\"--offline\"/>"));
+    }
+
+    #[test]
+    fn prometheus_exposes_counters_and_durations() {
+        let text = sample().to_prometheus();
+        assert!(text.contains("\ngraph_data_files_validated 3\n"));
+        assert!(text.contains("graph_data_checks{result=\"skipped\"} 1\n"));
+        assert!(text.contains(
+            "graph_data_check_duration_seconds{suite=\"verify_yaml\",check=\"dead-ends\"} 1.5\n"
+        ));
+        assert!(text.contains("graph_data_phase_duration_seconds{phase=\"verify_yaml\"} 2\n"));
+        assert!(!text.contains("phase=\"check_releases\""));
+    }
+}
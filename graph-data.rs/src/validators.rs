@@ -0,0 +1,186 @@
+//! Checks run against the raw YAML of every file, in addition to deserializing the typed models.
+
+use serde_yaml::Value;
+use std::fmt;
+use std::path::Path;
+
+/// A problem found by a `FileValidator`.
+#[derive(Debug)]
+pub struct ValidationError {
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// A check of one parsed YAML document.
+pub trait FileValidator {
+    fn validate(&self, path: &Path, value: &Value) -> Result<(), Vec<ValidationError>>;
+}
+
+/// Requires the document to be a mapping holding all of the given keys.
+pub struct RequiredKeys(pub Vec<&'static str>);
+
+impl FileValidator for RequiredKeys {
+    fn validate(&self, _path: &Path, value: &Value) -> Result<(), Vec<ValidationError>> {
+        let mapping = value.as_mapping().ok_or_else(|| {
+            vec![ValidationError {
+                message: "document is not a mapping".to_string(),
+            }]
+        })?;
+        let errors: Vec<ValidationError> = self
+            .0
+            .iter()
+            .filter(|key| !mapping.contains_key(&Value::String(key.to_string())))
+            .map(|key| ValidationError {
+                message: format!("missing required key '{}'", key),
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Rejects top-level keys other than the given ones, like a mistyped `verisons:`.
+pub struct KnownKeys(pub Vec<&'static str>);
+
+impl FileValidator for KnownKeys {
+    fn validate(&self, _path: &Path, value: &Value) -> Result<(), Vec<ValidationError>> {
+        let errors: Vec<ValidationError> = value
+            .as_mapping()
+            .into_iter()
+            .flat_map(|mapping| mapping.iter())
+            .filter(|(key, _)| match key.as_str() {
+                Some(key) => !self.0.iter().any(|known| *known == key),
+                None => true,
+            })
+            .map(|(key, _)| ValidationError {
+                message: format!("unknown key {:?}, expected one of {:?}", key, self.0),
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Rejects top-level keys without a value, like a `versions:` with nothing following it.
+pub struct NoNullValues;
+
+impl FileValidator for NoNullValues {
+    fn validate(&self, _path: &Path, value: &Value) -> Result<(), Vec<ValidationError>> {
+        let errors: Vec<ValidationError> = value
+            .as_mapping()
+            .into_iter()
+            .flat_map(|mapping| mapping.iter())
+            .filter(|(_, value)| value.is_null())
+            .map(|(key, _)| ValidationError {
+                message: format!("key {:?} has no value", key),
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// The keys of a channel file.
+pub const CHANNEL_KEYS: &[&str] = &["name", "versions"];
+
+/// The keys of a blocked edge file.
+pub const BLOCKED_EDGE_KEYS: &[&str] = &["to", "from"];
+
+/// Validators run against every channel file.
+pub fn channel_defaults() -> Vec<Box<dyn FileValidator>> {
+    vec![
+        Box::new(RequiredKeys(CHANNEL_KEYS.to_vec())),
+        Box::new(NoNullValues),
+    ]
+}
+
+/// Validators run against every blocked edge file.
+pub fn blocked_edge_defaults() -> Vec<Box<dyn FileValidator>> {
+    vec![
+        Box::new(RequiredKeys(BLOCKED_EDGE_KEYS.to_vec())),
+        Box::new(NoNullValues),
+    ]
+}
+
+/// Validators a user of the library runs in addition to the defaults, like one requiring an
+/// `owner` key in every channel file.
+#[derive(Default)]
+pub struct Custom {
+    /// Run against every channel file, including those of extra channel directories.
+    pub channels: Vec<Box<dyn FileValidator>>,
+    /// Run against every blocked edge file, including those of extra blocked edge directories.
+    pub blocked_edges: Vec<Box<dyn FileValidator>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(validator: &dyn FileValidator, yaml: &str) -> Result<(), Vec<String>> {
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        validator
+            .validate(Path::new("fast-4.5.yaml"), &value)
+            .map_err(|errors| errors.iter().map(ToString::to_string).collect())
+    }
+
+    #[test]
+    fn required_keys_must_all_be_present() {
+        let validator = RequiredKeys(CHANNEL_KEYS.to_vec());
+        assert_eq!(
+            validate(&validator, "name: fast-4.5\nversions: []\n"),
+            Ok(())
+        );
+        assert_eq!(
+            validate(&validator, "name: fast-4.5\n"),
+            Err(vec!["missing required key 'versions'".to_string()])
+        );
+        assert_eq!(
+            validate(&validator, "- fast-4.5\n"),
+            Err(vec!["document is not a mapping".to_string()])
+        );
+    }
+
+    #[test]
+    fn known_keys_reject_typos() {
+        let validator = KnownKeys(BLOCKED_EDGE_KEYS.to_vec());
+        assert_eq!(validate(&validator, "to: 4.5.1\nfrom: .*\n"), Ok(()));
+        let errors = validate(&validator, "to: 4.5.1\nfrom: .*\nform: .*\n").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("\"form\""), "{}", errors[0]);
+    }
+
+    #[test]
+    fn null_values_are_rejected() {
+        assert_eq!(validate(&NoNullValues, "name: fast-4.5\n"), Ok(()));
+        let errors = validate(&NoNullValues, "name: fast-4.5\nversions:\n").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("\"versions\""), "{}", errors[0]);
+    }
+
+    #[test]
+    fn defaults_require_the_model_keys() {
+        let yaml = "to: 4.5.1\n";
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let path = Path::new("4.5.1.yaml");
+        assert!(blocked_edge_defaults()
+            .iter()
+            .any(|validator| validator.validate(path, &value).is_err()));
+        assert!(channel_defaults()
+            .iter()
+            .any(|validator| validator.validate(path, &value).is_err()));
+    }
+}
@@ -1,13 +1,571 @@
+use crate::baseline;
+use crate::channels;
+use crate::checks::{self, Check};
+use crate::cli::Options;
+use crate::dump;
+use crate::history;
+use crate::report::{Counter, Problem, Report, Severity};
+use crate::validators::{self, Custom, FileValidator, KnownKeys};
 use cincinnati::plugins::internal::openshift_secondary_metadata_parser::plugin;
 use cincinnati::plugins::internal::openshift_secondary_metadata_parser::plugin::graph_data_model::{BlockedEdge, Channel};
 use regex::Regex;
-use semver::Version;
-use std::collections::HashSet;
-use std::path::PathBuf;
-use anyhow::Result as Fallible;
+use semver::{Identifier, Version, VersionReq};
+use serde::de::DeserializeOwned;
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Instant;
+use tracing::{debug, info};
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+use anyhow::{Context, Result as Fallible};
 
-pub async fn run() -> Fallible<HashSet<Version>> {
-    let data_dir = PathBuf::from("..");
+const SUITE: &str = "verify_yaml";
+
+/// How blocked edges are expected to spell the architecture of their `to` version.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArchConvention {
+    /// `to: 4.2.11`
+    Omit,
+    /// `to: 4.2.11+amd64`
+    Require,
+}
+
+impl FromStr for ArchConvention {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Fallible<Self> {
+        match s {
+            "omit" => Ok(ArchConvention::Omit),
+            "require" => Ok(ArchConvention::Require),
+            _ => Err(anyhow::anyhow!(
+                "unknown arch convention '{}', expected 'omit' or 'require'",
+                s
+            )),
+        }
+    }
+}
+
+/// A `major.minor` pair, as given to `--since`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Minor {
+    major: u64,
+    minor: u64,
+}
+
+impl Minor {
+    fn of(version: &Version) -> Self {
+        Minor {
+            major: version.major,
+            minor: version.minor,
+        }
+    }
+}
+
+impl FromStr for Minor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Fallible<Self> {
+        let mut parts = s.splitn(2, '.');
+        let (major, minor) = match (parts.next(), parts.next()) {
+            (Some(major), Some(minor)) => (major, minor),
+            _ => anyhow::bail!("expected MAJOR.MINOR, got '{}'", s),
+        };
+        Ok(Minor {
+            major: major.parse().context(format!("Parsing major of '{}'", s))?,
+            minor: minor.parse().context(format!("Parsing minor of '{}'", s))?,
+        })
+    }
+}
+
+/// Versions mentioned in the graph data, by where they were mentioned.
+#[derive(Debug, Default)]
+pub struct FoundVersions {
+    pub channels: HashSet<Version>,
+    pub blocked_edges: HashSet<Version>,
+}
+
+impl FoundVersions {
+    /// All mentioned versions.
+    pub fn all(&self) -> HashSet<Version> {
+        self.channels.union(&self.blocked_edges).cloned().collect()
+    }
+
+    /// Drop the versions excluded by `--since` or `--filter-version`, with `--channel` the
+    /// blocked edge versions which aren't in the selected channels, and those outside `--shard`.
+    fn retain_selected(&mut self, opts: &Options) {
+        self.channels.retain(|v| is_version_selected(opts, v));
+        self.blocked_edges.retain(|v| is_version_selected(opts, v));
+        if !opts.channels.is_empty() {
+            let channels = &self.channels;
+            self.blocked_edges.retain(|v| channels.contains(v));
+        }
+        if let Some(shard) = &opts.shard {
+            let all = self.all();
+            let mut sorted: Vec<&Version> = all.iter().collect();
+            sorted.sort_by(|a, b| a.cmp(b).then_with(|| a.to_string().cmp(&b.to_string())));
+            let kept: HashSet<&Version> = sorted
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| i % shard.count == shard.index - 1)
+                .map(|(_, v)| v)
+                .collect();
+            self.channels.retain(|v| kept.contains(v));
+            self.blocked_edges.retain(|v| kept.contains(v));
+        }
+    }
+}
+
+/// A `--shard` like `2/8`: the second of eight disjoint parts of the versions to check.
+///
+/// Versions are dealt out round-robin in version order, so every job given the same graph data
+/// agrees on the parts.
+#[derive(Debug)]
+pub struct Shard {
+    /// The part to keep, counting from 1.
+    pub index: usize,
+    pub count: usize,
+}
+
+impl FromStr for Shard {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Fallible<Self> {
+        let mut parts = s.splitn(2, '/');
+        let (index, count) = match (parts.next(), parts.next()) {
+            (Some(index), Some(count)) => (index, count),
+            _ => anyhow::bail!("expected INDEX/COUNT, got '{}'", s),
+        };
+        let shard = Shard {
+            index: index.parse().context(format!("Parsing index of '{}'", s))?,
+            count: count.parse().context(format!("Parsing count of '{}'", s))?,
+        };
+        if shard.index == 0 || shard.index > shard.count {
+            anyhow::bail!("expected an index from 1 to {}, got '{}'", shard.count, s);
+        }
+        Ok(shard)
+    }
+}
+
+/// A `--filter-version` pattern: a glob over the whole version like `4.5.*` or `*+s390x` if it
+/// contains `*` or `?`, and a semver range like `>=4.5, <4.7` otherwise.
+#[derive(Debug)]
+pub enum VersionFilter {
+    Glob(Regex),
+    Range(VersionReq),
+}
+
+impl VersionFilter {
+    pub fn matches(&self, version: &Version) -> bool {
+        match self {
+            VersionFilter::Glob(glob) => glob.is_match(&version.to_string()),
+            VersionFilter::Range(range) => range.matches(version),
+        }
+    }
+}
+
+impl FromStr for VersionFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Fallible<Self> {
+        if !s.contains('*') && !s.contains('?') {
+            let range = VersionReq::parse(s).context(format!("Parsing '{}' as a range", s))?;
+            return Ok(VersionFilter::Range(range));
+        }
+        let mut pattern = String::from("^");
+        for c in s.chars() {
+            match c {
+                '*' => pattern.push_str(".*"),
+                '?' => pattern.push('.'),
+                c => pattern.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        pattern.push('$');
+        Ok(VersionFilter::Glob(Regex::new(&pattern)?))
+    }
+}
+
+/// Whether the channel file at `path` was selected by `--channel`.
+fn is_channel_selected(opts: &Options, path: &Path) -> bool {
+    if opts.channels.is_empty() {
+        return true;
+    }
+    let stem = path.file_stem().and_then(|stem| stem.to_str());
+    opts.channels.iter().any(|name| Some(name.as_str()) == stem)
+}
+
+/// The minor of the channel file at `path`, e.g. `4.5` for `stable-4.5.yaml`.
+fn channel_minor(path: &Path) -> Option<&str> {
+    let stem = path.file_stem()?.to_str()?;
+    channels::split_name(stem).map(|(_, minor)| minor)
+}
+
+/// The files selected by `--channel` and `--staged`.
+struct Selection<'a> {
+    opts: &'a Options,
+    /// The staged files below the data directory, `None` without `--staged`.
+    staged: Option<HashSet<PathBuf>>,
+}
+
+impl<'a> Selection<'a> {
+    fn new(opts: &'a Options, data_dir: &Path) -> Fallible<Self> {
+        let staged = if opts.staged {
+            Some(history::staged_files(data_dir)?)
+        } else {
+            None
+        };
+        Ok(Selection { opts, staged })
+    }
+
+    /// Whether to parse the channel file at `path`.
+    ///
+    /// With `--staged`, staged channels are parsed along with the other tiers of their minor, so
+    /// the checks spanning tiers still see them.
+    fn keeps_channel(&self, path: &Path) -> bool {
+        if !is_channel_selected(self.opts, path) {
+            return false;
+        }
+        let staged = match &self.staged {
+            Some(staged) => staged,
+            None => return true,
+        };
+        let minor = channel_minor(path);
+        minor.is_some()
+            && staged
+                .iter()
+                .any(|staged| staged.parent() == path.parent() && channel_minor(staged) == minor)
+    }
+
+    /// With `--base-ref` and without `--all`, drop the found versions which were already in the
+    /// same files at the base ref.
+    fn retain_new(
+        &self,
+        found_versions: &mut FoundVersions,
+        channels_vec: &[(PathBuf, Channel)],
+        blocked_edge_vec: &[(PathBuf, BlockedEdge)],
+    ) -> Fallible<()> {
+        let rev = match &self.opts.base_ref {
+            Some(rev) if !self.opts.all => rev,
+            _ => return Ok(()),
+        };
+        let base = history::versions_at(channels_vec, blocked_edge_vec, rev)?;
+        found_versions.channels.retain(|v| !base.contains(v));
+        found_versions.blocked_edges.retain(|v| !base.contains(v));
+        info!(
+            "Found {} versions new since {}",
+            found_versions.all().len(),
+            rev
+        );
+        Ok(())
+    }
+
+    /// With `--staged`, drop the found versions which aren't in any staged file.
+    fn retain_staged(
+        &self,
+        found_versions: &mut FoundVersions,
+        channels_vec: &[(PathBuf, Channel)],
+        blocked_edge_vec: &[(PathBuf, BlockedEdge)],
+    ) {
+        let staged = match &self.staged {
+            Some(staged) => staged,
+            None => return,
+        };
+        let channels: HashSet<&Version> = channels_vec
+            .iter()
+            .filter(|(path, _)| staged.contains(path))
+            .flat_map(|(_, channel)| channel.versions.iter())
+            .collect();
+        found_versions.channels.retain(|v| channels.contains(v));
+        let blocked_edges: HashSet<&Version> = blocked_edge_vec
+            .iter()
+            .filter(|(path, _)| staged.contains(path))
+            .map(|(_, edge)| &edge.to)
+            .collect();
+        found_versions
+            .blocked_edges
+            .retain(|v| blocked_edges.contains(v));
+    }
+}
+
+/// Fail if any channel given to `--channel` has no file.
+fn check_channels_found(opts: &Options, channels: &[(PathBuf, Channel)]) -> Fallible<()> {
+    let missing: Vec<&str> = opts
+        .channels
+        .iter()
+        .filter(|name| {
+            !channels.iter().any(|(path, _)| {
+                path.file_stem().and_then(|stem| stem.to_str()) == Some(name.as_str())
+            })
+        })
+        .map(String::as_str)
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "No channel file found for: {}",
+            missing.join(", ")
+        ))
+    }
+}
+
+/// Whether `version` passes `--since` and `--filter-version`.
+fn is_version_selected(opts: &Options, version: &Version) -> bool {
+    opts.since.map_or(true, |since| Minor::of(version) >= since)
+        && opts
+            .filter_version
+            .as_ref()
+            .map_or(true, |filter| filter.matches(version))
+}
+
+/// One-line summary of a parsed file, logged at debug level.
+trait Describe {
+    fn describe(&self) -> String;
+}
+
+impl Describe for Channel {
+    fn describe(&self) -> String {
+        format!(
+            "channel {} with {} versions",
+            self.name,
+            self.versions.len()
+        )
+    }
+}
+
+impl Describe for BlockedEdge {
+    fn describe(&self) -> String {
+        format!("blocks edges from '{}' to {}", self.from, self.to)
+    }
+}
+
+/// Resolve the file a symlink points to, failing clearly if it dangles or loops.
+async fn resolve_symlink(path: &Path) -> Fallible<PathBuf> {
+    tokio::fs::canonicalize(path)
+        .await
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => anyhow::anyhow!("{:?} is a dangling symlink", path),
+            _ => anyhow::anyhow!("{:?} can't be resolved, is it a symlink loop? {}", path, e),
+        })
+}
+
+fn has_yaml_extension(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("yaml") | Some("yml") => true,
+        _ => false,
+    }
+}
+
+/// Resolve the directory `name` below `data_dir`, failing if it is missing or holds no YAML files.
+async fn data_subdir(data_dir: &Path, name: &Path) -> Fallible<PathBuf> {
+    let path = data_dir.join(name);
+    if !path.exists() {
+        anyhow::bail!("{:?} directory not found at {:?}", name, path);
+    }
+    if !path.is_dir() {
+        anyhow::bail!("{:?} is not a directory", path);
+    }
+    let path = path.canonicalize()?;
+    let mut entries = tokio::fs::read_dir(&path)
+        .await
+        .context(format!("Reading directory {:?}", path))?;
+    while let Some(entry) = entries.next_entry().await? {
+        if has_yaml_extension(&entry.path()) {
+            return Ok(path);
+        }
+    }
+    anyhow::bail!("{:?} directory at {:?} contains no YAML files", name, path)
+}
+
+/// `error` followed by the line of `contents` it is about and a caret under its column, so
+/// the problem can be fixed without opening the file.
+fn with_snippet(error: serde_yaml::Error, contents: &[u8]) -> anyhow::Error {
+    let location = match error.location() {
+        Some(location) => location,
+        None => return error.into(),
+    };
+    let text = String::from_utf8_lossy(contents);
+    let line = match text.lines().nth(location.line().saturating_sub(1)) {
+        Some(line) => line,
+        None => return error.into(),
+    };
+    let number = location.line().to_string();
+    anyhow::anyhow!(
+        "{}\n {} | {}\n {} | {}^",
+        error,
+        number,
+        line,
+        " ".repeat(number.len()),
+        " ".repeat(location.column().saturating_sub(1))
+    )
+}
+
+/// The line each scalar of a file is first found on.
+type ScalarLines = HashMap<String, usize>;
+
+/// The scalar lines of every parsed file, to point the findings of the checks at the line of the
+/// version they are about.
+#[derive(Default)]
+struct Lines(HashMap<PathBuf, ScalarLines>);
+
+impl Lines {
+    /// Add the line to each of `problems` about a version in a parsed file which has none yet.
+    fn locate(&self, problems: Vec<Problem>) -> Vec<Problem> {
+        problems
+            .into_iter()
+            .map(|problem| {
+                let line = match (&problem.file, &problem.version, problem.line) {
+                    (Some(file), Some(version), None) => self
+                        .0
+                        .get(file)
+                        .and_then(|scalars| scalars.get(&version.to_string())),
+                    _ => None,
+                };
+                match line {
+                    Some(&line) => problem.at_line(line),
+                    None => problem,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Scans a YAML event stream for what serde_yaml would silently misread: keys repeated within a
+/// mapping, which it resolves to the last value, and `<<` merge keys, which it keeps as ordinary
+/// keys instead of expanding them. Also notes the line of every scalar along the way.
+#[derive(Default)]
+struct Scan {
+    /// The scalar keys seen so far in each open mapping, and whether its next node is a key,
+    /// `None` for open sequences.
+    open: Vec<Option<(HashSet<String>, bool)>>,
+    /// The line and key of every repeated key.
+    repeated: Vec<(usize, String)>,
+    /// The line of every merge key.
+    merges: Vec<usize>,
+    lines: ScalarLines,
+}
+
+impl Scan {
+    /// Account for a complete node, which is `key` if it is a scalar.
+    fn node(&mut self, key: Option<String>, mark: Marker) {
+        if let Some(Some((keys, is_key))) = self.open.last_mut() {
+            if *is_key {
+                match key {
+                    Some(key) if key == "<<" => self.merges.push(mark.line()),
+                    Some(key) => {
+                        if !keys.insert(key.clone()) {
+                            self.repeated.push((mark.line(), key));
+                        }
+                    }
+                    None => {}
+                }
+            }
+            *is_key = !*is_key;
+        }
+    }
+}
+
+impl MarkedEventReceiver for Scan {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::DocumentStart => self.open.clear(),
+            Event::MappingStart(_) => self.open.push(Some((HashSet::new(), true))),
+            Event::SequenceStart(_) => self.open.push(None),
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.open.pop();
+                self.node(None, mark);
+            }
+            Event::Scalar(value, ..) => {
+                self.lines
+                    .entry(value.clone())
+                    .or_insert_with(|| mark.line());
+                self.node(Some(value), mark);
+            }
+            Event::Alias(_) => self.node(None, mark),
+            _ => {}
+        }
+    }
+}
+
+/// The line of each scalar in `contents` and of each `<<` merge key, which serde_yaml doesn't
+/// expand itself, failing if a mapping repeats a key, e.g. a second `versions:` replacing the first.
+///
+/// Syntax errors are left for serde_yaml to report.
+fn scan(path: &Path, contents: &[u8]) -> Result<(ScalarLines, Vec<usize>), Problem> {
+    let text = match std::str::from_utf8(contents) {
+        Ok(text) => text,
+        Err(_) => return Ok((ScalarLines::new(), vec![])),
+    };
+    let mut scan = Scan::default();
+    if Parser::new(text.chars()).load(&mut scan, true).is_err() {
+        return Ok((ScalarLines::new(), vec![]));
+    }
+    if let Some(&(line, _)) = scan.repeated.first() {
+        let repeated: Vec<String> = scan
+            .repeated
+            .iter()
+            .map(|(line, key)| format!("line {}: key '{}'", line, key))
+            .collect();
+        return Err(Problem::new(format!(
+            "{:?} repeats keys, keeping only their last value:\n{}",
+            path,
+            repeated.join("\n")
+        ))
+        .in_file(path)
+        .at_line(line));
+    }
+    Ok((scan.lines, scan.merges))
+}
+
+/// Expand the `<<` merge keys of `value` and of every value nested in it.
+///
+/// A merge key refers to a mapping or a sequence of mappings. The keys of the mapping holding it
+/// win over the merged ones, and earlier merged mappings win over later ones.
+fn expand_merges(value: Value) -> Fallible<Value> {
+    let mapping = match value {
+        Value::Mapping(mapping) => mapping,
+        Value::Sequence(values) => {
+            let values: Fallible<Vec<Value>> = values.into_iter().map(expand_merges).collect();
+            return values.map(Value::Sequence);
+        }
+        value => return Ok(value),
+    };
+    let merge_key = Value::String("<<".to_string());
+    let mut expanded = Mapping::new();
+    let mut merged = vec![];
+    for (key, value) in mapping {
+        let value = expand_merges(value)?;
+        if key != merge_key {
+            expanded.insert(key, value);
+            continue;
+        }
+        let mappings = match value {
+            Value::Sequence(values) => values,
+            value => vec![value],
+        };
+        for mapping in mappings {
+            match mapping {
+                Value::Mapping(mapping) => merged.push(mapping),
+                _ => anyhow::bail!("a '<<' merge key must refer to mappings"),
+            }
+        }
+    }
+    for mapping in merged {
+        for (key, value) in mapping {
+            if !expanded.contains_key(&key) {
+                expanded.insert(key, value);
+            }
+        }
+    }
+    Ok(Value::Mapping(expanded))
+}
+
+/// Deserialize all files in `dir` with the graph data loader, the way Cincinnati reads them.
+async fn deserialize_with_loader<T>(dir: &Path) -> Fallible<Vec<T>>
+where
+    T: DeserializeOwned,
+{
     let all_files_regex = Regex::new(".*")?;
     let disallowed_errors: HashSet<plugin::DeserializeDirectoryFilesErrorDiscriminants> = [
         plugin::DeserializeDirectoryFilesErrorDiscriminants::File,
@@ -18,34 +576,1700 @@ pub async fn run() -> Fallible<HashSet<Version>> {
     .iter()
     .cloned()
     .collect();
-    // Collect a list of mentioned versions
-    let mut found_versions: HashSet<Version> = HashSet::new();
-
-    println!("Verifying blocked edge files are valid");
-    let blocked_edge_path = data_dir.join(plugin::BLOCKED_EDGES_DIR).canonicalize()?;
-    let blocked_edge_vec = plugin::deserialize_directory_files::<BlockedEdge>(
-        &blocked_edge_path,
-        all_files_regex.clone(),
+    let values = plugin::deserialize_directory_files::<T>(
+        &dir.to_path_buf(),
+        all_files_regex,
         &disallowed_errors,
     )
     .await?;
-    for v in blocked_edge_vec.iter() {
-        found_versions.insert(v.to.clone());
+    Ok(values)
+}
+
+/// The files of a directory which parsed, and a problem for each one which didn't.
+struct Parsed<T> {
+    files: Vec<(PathBuf, T)>,
+    lines: Lines,
+    problems: Vec<Problem>,
+}
+
+impl<T> Parsed<T> {
+    /// The files, or an error listing every problem if there are any.
+    fn into_result(self) -> Fallible<Vec<(PathBuf, T)>> {
+        if self.problems.is_empty() {
+            return Ok(self.files);
+        }
+        let problems: Vec<String> = self.problems.iter().map(ToString::to_string).collect();
+        Err(anyhow::anyhow!("{}", problems.join("\n")))
     }
+}
 
-    println!("Verifying channel files are valid");
-    let channel_path = data_dir.join(plugin::CHANNELS_DIR).canonicalize()?;
-    let channels_vec = plugin::deserialize_directory_files::<Channel>(
-        &channel_path,
-        all_files_regex.clone(),
-        &disallowed_errors,
-    )
-    .await?;
-    for c in channels_vec.iter() {
+/// Deserialize the YAML file at `path`, which is a symlink if `is_symlink`, expanding its merge
+/// keys and checking it with all of `validators`; also returns the line of each of its scalars
+/// and of its first merge key.
+async fn parse_file<T>(
+    path: &Path,
+    is_symlink: bool,
+    validators: &Validators<'_>,
+) -> Result<(T, ScalarLines, Option<usize>), Problem>
+where
+    T: DeserializeOwned,
+{
+    let problem = |e: anyhow::Error| Problem::new(format!("{:#}", e)).in_file(path);
+    let target = if is_symlink {
+        resolve_symlink(path).await.map_err(problem)?
+    } else {
+        path.to_path_buf()
+    };
+    match target.extension().and_then(|ext| ext.to_str()) {
+        Some("yaml") | Some("yml") => {}
+        Some(ext) => {
+            return Err(problem(anyhow::anyhow!(
+                "{:?} has invalid extension '{}'",
+                path,
+                ext
+            )))
+        }
+        None => {
+            return Err(problem(anyhow::anyhow!(
+                "{:?} is missing an extension",
+                path
+            )))
+        }
+    }
+    let contents = tokio::fs::read(&target)
+        .await
+        .context(format!("Reading {:?}", path))
+        .map_err(problem)?;
+    let (lines, merges) = scan(path, &contents)?;
+    let deserialize_error = |e: serde_yaml::Error| {
+        let line = e.location().map(|location| location.line());
+        let e = if e.to_string().contains("unknown anchor") {
+            anyhow::anyhow!("{:?} uses an alias to an undefined anchor: {}", path, e)
+        } else {
+            with_snippet(e, &contents).context(format!("Deserializing {:?}", path))
+        };
+        match line {
+            Some(line) => problem(e).at_line(line),
+            None => problem(e),
+        }
+    };
+    let merge = merges.first().copied();
+    let raw = if merge.is_none() && validators.iter().next().is_none() {
+        None
+    } else {
+        let raw: Value = serde_yaml::from_slice(&contents).map_err(deserialize_error)?;
+        let expanded = expand_merges(raw).map_err(|e| {
+            let problem = problem(e.context(format!("Expanding the merge keys of {:?}", path)));
+            match merge {
+                Some(line) => problem.at_line(line),
+                None => problem,
+            }
+        })?;
+        Some(expanded)
+    };
+    let value: T = match (&raw, merge) {
+        (Some(raw), Some(_)) => serde_yaml::from_value(raw.clone()).map_err(|e| {
+            problem(anyhow::Error::from(e).context(format!("Deserializing {:?}", path)))
+        })?,
+        // The same deserialization the loader performs, here with the path at hand
+        _ => serde_yaml::from_slice(&contents).map_err(deserialize_error)?,
+    };
+    if let Some(raw) = raw {
+        let errors: Vec<String> = validators
+            .iter()
+            .filter_map(|validator| validator.validate(path, &raw).err())
+            .flatten()
+            .map(|e| e.to_string())
+            .collect();
+        if !errors.is_empty() {
+            return Err(problem(anyhow::anyhow!(
+                "{:?} is invalid:\n{}",
+                path,
+                errors.join("\n")
+            )));
+        }
+    }
+    Ok((value, lines, merge))
+}
+
+/// Deserialize all YAML files in `dir`, keeping the path each value was read from.
+///
+/// The graph data loader decides whether the files are valid, as it is what Cincinnati reads them
+/// with. It only returns the values though, so each file is also read on its own to keep its path
+/// and to point at the file and line of a problem. Repeated keys, which the loader would silently
+/// misread, are rejected as well, and each file is checked by all of `validators`. Merge keys are
+/// expanded, but as the loader doesn't, its failure is put down to the files using them.
+/// Symlinks are followed, but the returned paths and any problems refer to the link itself.
+/// Files for which `keep` returns false are skipped without being read on their own, but a
+/// problem is still reported for the directory if the loader rejects them.
+///
+/// Only fails if `dir` can't be listed; every file which doesn't parse becomes one of the
+/// returned problems.
+async fn walk_files<T>(
+    dir: &Path,
+    validators: &Validators<'_>,
+    keep: &dyn Fn(&Path) -> bool,
+) -> Fallible<Parsed<T>>
+where
+    T: DeserializeOwned + Describe,
+{
+    let mut entries = tokio::fs::read_dir(dir)
+        .await
+        .context(format!("Reading directory {:?}", dir))?;
+    let mut dir_entries = vec![];
+    while let Some(entry) = entries.next_entry().await? {
+        dir_entries.push(entry);
+    }
+    // Walk in a stable order so problems are reported the same way on every run
+    dir_entries.sort_by_key(|entry| entry.path());
+
+    // The loader reads the whole directory, so it only speaks for a selection of all the files
+    let loaded = if dir_entries.iter().all(|entry| keep(&entry.path())) {
+        Some(deserialize_with_loader::<T>(dir).await)
+    } else {
+        None
+    };
+
+    let mut parsed = Parsed {
+        files: vec![],
+        lines: Lines::default(),
+        problems: vec![],
+    };
+    let mut merged = vec![];
+    for entry in dir_entries {
+        let path = entry.path();
+        if !keep(&path) {
+            continue;
+        }
+        let is_symlink = entry.file_type().await?.is_symlink();
+        match parse_file::<T>(&path, is_symlink, validators).await {
+            Ok((value, lines, merge)) => {
+                debug!("{:?}: {}", path, value.describe());
+                if let Some(line) = merge {
+                    merged.push((path.clone(), line));
+                }
+                parsed.lines.0.insert(path.clone(), lines);
+                parsed.files.push((path, value));
+            }
+            Err(problem) => parsed.problems.push(problem),
+        }
+    }
+
+    // The problems of single files explain why the loader failed better than it does itself
+    if let Some(Err(e)) = loaded {
+        if parsed.problems.is_empty() && merged.is_empty() {
+            parsed.problems.push(Problem::new(format!(
+                "The graph data loader rejected {:?}: {:#}",
+                dir, e
+            )));
+        }
+        if parsed.problems.is_empty() {
+            for (path, line) in merged {
+                parsed.problems.push(
+                    Problem::new(format!(
+                        "{:?} uses a '<<' merge key at line {}, which the graph data loader \
+                         doesn't expand: {:#}",
+                        path, line, e
+                    ))
+                    .in_file(&path)
+                    .at_line(line),
+                );
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+/// An additional directory whose files must deserialize into one of the graph data models.
+///
+/// Parsed from `DIR=MODEL`, where `DIR` is relative to the data root and `MODEL` is
+/// `channel` or `blocked-edge`.
+#[derive(Debug)]
+pub struct Target {
+    dir: PathBuf,
+    model: Model,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Model {
+    Channel,
+    BlockedEdge,
+}
+
+/// The validators run against the files of one model.
+struct Validators<'a> {
+    defaults: Vec<Box<dyn FileValidator>>,
+    custom: &'a [Box<dyn FileValidator>],
+}
+
+impl Validators<'_> {
+    /// The defaults, followed by the custom validators.
+    fn iter(&self) -> impl Iterator<Item = &dyn FileValidator> {
+        self.defaults
+            .iter()
+            .chain(self.custom.iter())
+            .map(|validator| validator.as_ref())
+    }
+}
+
+impl Model {
+    /// The validators run against files of this model, rejecting unknown keys if
+    /// `deny_unknown_fields`, and then those of `custom` for this model.
+    fn validators(self, deny_unknown_fields: bool, custom: &Custom) -> Validators<'_> {
+        let (mut defaults, keys, custom) = match self {
+            Model::Channel => (
+                validators::channel_defaults(),
+                validators::CHANNEL_KEYS,
+                &custom.channels,
+            ),
+            Model::BlockedEdge => (
+                validators::blocked_edge_defaults(),
+                validators::BLOCKED_EDGE_KEYS,
+                &custom.blocked_edges,
+            ),
+        };
+        if deny_unknown_fields {
+            defaults.push(Box::new(KnownKeys(keys.to_vec())));
+        }
+        Validators { defaults, custom }
+    }
+}
+
+impl FromStr for Target {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Fallible<Self> {
+        let mut parts = s.splitn(2, '=');
+        let dir = parts.next().unwrap_or_default();
+        let model = match parts.next() {
+            Some("channel") => Model::Channel,
+            Some("blocked-edge") => Model::BlockedEdge,
+            Some(model) => anyhow::bail!(
+                "unknown model '{}', expected 'channel' or 'blocked-edge'",
+                model
+            ),
+            None => anyhow::bail!("expected DIR=MODEL, got '{}'", s),
+        };
+        Ok(Target {
+            dir: PathBuf::from(dir),
+            model,
+        })
+    }
+}
+
+fn is_arch(identifier: &Identifier, arches: &[String]) -> bool {
+    match identifier {
+        Identifier::AlphaNumeric(s) => arches.iter().any(|arch| arch == s),
+        Identifier::Numeric(_) => false,
+    }
+}
+
+/// Report versions whose build metadata is anything other than a single architecture from `arches`.
+fn check_build_metadata<'a>(
+    versions: impl Iterator<Item = (&'a Path, &'a Version)>,
+    arches: &[String],
+) -> Vec<Problem> {
+    let mut errors = vec![];
+    for (path, version) in versions {
+        match version.build.as_slice() {
+            [] => {}
+            [identifier] if is_arch(identifier, arches) => {}
+            build => {
+                let build: Vec<String> = build.iter().map(ToString::to_string).collect();
+                errors.push(
+                    Problem::new(format!(
+                        "{:?}: '{}' has unrecognized build metadata '{}'",
+                        path,
+                        version,
+                        build.join(".")
+                    ))
+                    .in_file(path)
+                    .about(version),
+                );
+            }
+        }
+    }
+    errors
+}
+
+/// Plausible ranges for version components, anything outside of them is most likely a typo.
+#[derive(Debug)]
+struct Bounds {
+    major: (u64, u64),
+    max_minor: u64,
+    max_patch: u64,
+}
+
+/// Report versions with a component outside of `bounds`, like `44.11.0` or `4.110.0`.
+fn check_bounds<'a>(
+    versions: impl Iterator<Item = (&'a Path, &'a Version)>,
+    bounds: &Bounds,
+) -> Vec<Problem> {
+    versions
+        .filter(|(_, v)| {
+            v.major < bounds.major.0
+                || v.major > bounds.major.1
+                || v.minor > bounds.max_minor
+                || v.patch > bounds.max_patch
+        })
+        .map(|(path, v)| {
+            Problem::new(format!("{:?}: '{}' looks implausible", path, v))
+                .in_file(path)
+                .about(v)
+        })
+        .collect()
+}
+
+/// Report blocked edges whose `to` version does not follow `convention`.
+///
+/// Architectures spelled as pre-release identifiers (`4.2.11-s390x`) are never canonical.
+fn check_blocked_edge_arch(
+    edges: &[(PathBuf, BlockedEdge)],
+    convention: ArchConvention,
+    arches: &[String],
+) -> Vec<Problem> {
+    let mut errors = vec![];
+    for (path, edge) in edges.iter() {
+        let version = &edge.to;
+        let message = if version.pre.iter().any(|id| is_arch(id, arches)) {
+            "carries its architecture as a pre-release, use '+<arch>' instead"
+        } else {
+            match (convention, version.build.is_empty()) {
+                (ArchConvention::Omit, false) => "should not have an architecture suffix",
+                (ArchConvention::Require, true) => "is missing an architecture suffix",
+                _ => continue,
+            }
+        };
+        errors.push(
+            Problem::new(format!("{:?}: '{}' {}", path, version, message))
+                .in_file(path)
+                .about(version),
+        );
+    }
+    errors
+}
+
+/// Record the check `name`, failing with `summary` followed by every one of `errors` if there are any.
+fn check(report: &Report, name: &str, summary: &str, mut errors: Vec<Problem>) -> Fallible<()> {
+    errors.sort_by(|a, b| a.message.cmp(&b.message));
+    let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+    for error in errors {
+        report.finding(SUITE, name, Severity::Error, error);
+    }
+    let result = if messages.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("{}:\n{}", summary, messages.join("\n")))
+    };
+    report.record_result(SUITE, name, result)
+}
+
+/// Print `warnings` under `summary` and record the check `name` as passed, or fail like `check` if `strict`.
+fn warn(
+    report: &Report,
+    name: &str,
+    summary: &str,
+    mut warnings: Vec<Problem>,
+    strict: bool,
+) -> Fallible<()> {
+    if strict {
+        return check(report, name, summary, warnings);
+    }
+    warnings.sort_by(|a, b| a.message.cmp(&b.message));
+    for warning in warnings {
+        tracing::warn!("{}: {}", summary, warning);
+        report.finding(SUITE, name, Severity::Warning, warning);
+    }
+    report.record(SUITE, name, None);
+    Ok(())
+}
+
+/// Parses the graph data and runs the checks selected on the command line on it, recording their
+/// outcomes in the report.
+///
+/// Unless `--fail-fast` is given, invalid files and failed checks are collected so the remaining
+/// ones still run.
+struct Checks<'a> {
+    opts: &'a Options,
+    report: &'a Report,
+    /// The lines of the parsed files, to point findings at.
+    lines: Lines,
+    failures: Vec<anyhow::Error>,
+}
+
+impl<'a> Checks<'a> {
+    fn new(opts: &'a Options, report: &'a Report) -> Self {
+        Checks {
+            opts,
+            report,
+            lines: Lines::default(),
+            failures: vec![],
+        }
+    }
+
+    /// Announce `check` and return when it started, or `None` if it isn't selected.
+    fn start(&self, check: Check) -> Option<Instant> {
+        if !self.opts.is_selected(check) {
+            return None;
+        }
+        info!("Verifying {}", check.description());
+        Some(Instant::now())
+    }
+
+    /// Run `check` if selected, failing with `summary` if `problems` finds any.
+    fn error(
+        &mut self,
+        check: Check,
+        summary: &str,
+        problems: impl FnOnce() -> Vec<Problem>,
+    ) -> Fallible<()> {
+        let started = match self.start(check) {
+            Some(started) => started,
+            None => return Ok(()),
+        };
+        let (report, lines, name) = (self.report, &self.lines, check.name());
+        let result = tracing::info_span!("check", check = name)
+            .in_scope(|| self::check(report, name, summary, lines.locate(problems())));
+        self.finish(check, started, result)
+    }
+
+    /// Run `check` if selected, only printing the problems `problems` finds unless `--strict`.
+    fn warning(
+        &mut self,
+        check: Check,
+        summary: &str,
+        problems: impl FnOnce() -> Vec<Problem>,
+    ) -> Fallible<()> {
+        let started = match self.start(check) {
+            Some(started) => started,
+            None => return Ok(()),
+        };
+        let (report, lines, strict) = (self.report, &self.lines, self.opts.strict);
+        let name = check.name();
+        let result = tracing::info_span!("check", check = name)
+            .in_scope(|| warn(report, name, summary, lines.locate(problems()), strict));
+        self.finish(check, started, result)
+    }
+
+    /// Record how long `check` took since `started`, then pass `result` on to `collect`.
+    fn finish(&mut self, check: Check, started: Instant, result: Fallible<()>) -> Fallible<()> {
+        self.report
+            .time_check(SUITE, check.name(), started.elapsed());
+        self.collect(result)
+    }
+
+    /// Pass `result` through with `--fail-fast`, or keep its failure for `into_result`.
+    fn collect(&mut self, result: Fallible<()>) -> Fallible<()> {
+        match result {
+            Err(e) if !self.opts.fail_fast => {
+                self.failures.push(e);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Parse all files in the directory `name` below `data_dir`, recording a passing case for each
+    /// one which parsed, and a case for the directory which fails if any file didn't.
+    ///
+    /// Returns the files which parsed, so the checks still run on them without `--fail-fast`.
+    /// Without `yaml-syntax`, the files which didn't parse are only skipped.
+    async fn parse<T>(
+        &mut self,
+        data_dir: &Path,
+        name: &Path,
+        validators: &Validators<'_>,
+        keep: &dyn Fn(&Path) -> bool,
+    ) -> Fallible<Vec<(PathBuf, T)>>
+    where
+        T: DeserializeOwned + Describe,
+    {
+        let report = self.report;
+        let dir = data_dir.join(name);
+        let parsed = match data_subdir(data_dir, name).await {
+            Ok(path) => walk_files::<T>(&path, validators, keep).await,
+            Err(e) => Err(e),
+        };
+        let parsed = parsed.unwrap_or_else(|e| Parsed {
+            files: vec![],
+            lines: Lines::default(),
+            problems: vec![Problem::new(format!("{:#}", e))],
+        });
+        self.lines.0.extend(parsed.lines.0);
+        if !self.opts.is_selected(Check::YamlSyntax) {
+            for problem in parsed.problems.iter() {
+                debug!("Skipping an invalid file: {}", problem);
+            }
+            return Ok(parsed.files);
+        }
+        for (path, _) in parsed.files.iter() {
+            report.record(SUITE, path.display().to_string(), None);
+        }
+        report.count(Counter::FilesValidated, parsed.files.len());
+        let messages: Vec<String> = parsed.problems.iter().map(ToString::to_string).collect();
+        for problem in parsed.problems {
+            report.finding(SUITE, Check::YamlSyntax.name(), Severity::Error, problem);
+        }
+        let result = if messages.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "Found invalid files in {:?}:\n{}",
+                dir,
+                messages.join("\n")
+            ))
+        };
+        let result = report.record_result(SUITE, dir.display().to_string(), result);
+        self.collect(result)?;
+        Ok(parsed.files)
+    }
+
+    /// Fail with all collected failures, if there are any.
+    fn into_result(self) -> Fallible<()> {
+        if self.failures.is_empty() {
+            return Ok(());
+        }
+        let failures: Vec<String> = self.failures.iter().map(|e| format!("{:#}", e)).collect();
+        Err(anyhow::anyhow!("{}", failures.join("\n\n")))
+    }
+}
+
+/// Parse and check all graph data files, running the validators of `custom` besides the defaults.
+pub async fn run(opts: &Options, report: &Report, custom: &Custom) -> Fallible<FoundVersions> {
+    let data_dir = canonical_data_dir(opts)?;
+    if !data_dir.is_dir() {
+        anyhow::bail!("Data directory {:?} is not a directory", data_dir);
+    }
+    // Collect a list of mentioned versions
+    let mut found_versions = FoundVersions::default();
+
+    let selection = Selection::new(opts, &data_dir)?;
+    let mut checks = Checks::new(opts, report);
+
+    info!("Verifying blocked edge files are valid");
+    let blocked_edge_vec = checks
+        .parse::<BlockedEdge>(
+            &data_dir,
+            Path::new(plugin::BLOCKED_EDGES_DIR),
+            &Model::BlockedEdge.validators(opts.deny_unknown_fields, custom),
+            &|_| true,
+        )
+        .await?;
+    for (_, v) in blocked_edge_vec.iter() {
+        found_versions.blocked_edges.insert(v.to.clone());
+    }
+
+    info!("Verifying channel files are valid");
+    let channels_vec = checks
+        .parse::<Channel>(
+            &data_dir,
+            Path::new(plugin::CHANNELS_DIR),
+            &Model::Channel.validators(opts.deny_unknown_fields, custom),
+            &|path| selection.keeps_channel(path),
+        )
+        .await?;
+    check_channels_found(opts, &channels_vec)?;
+    for (_, c) in channels_vec.iter() {
         for v in c.versions.iter() {
-            found_versions.insert(v.clone());
+            found_versions.channels.insert(v.clone());
+        }
+    }
+
+    if let Some(path) = &opts.dump_graph {
+        let rendered = dump::render(&channels_vec, &blocked_edge_vec);
+        if path == Path::new("-") {
+            print!("{}", rendered);
+        } else {
+            tokio::fs::write(path, rendered)
+                .await
+                .context(format!("Writing graph dump to {:?}", path))?;
+        }
+    }
+
+    for target in opts.extra_targets.iter() {
+        info!(
+            "Verifying {:?} files in {:?} are valid",
+            target.model, target.dir
+        );
+        let validators = target.model.validators(opts.deny_unknown_fields, custom);
+        match target.model {
+            Model::Channel => {
+                checks
+                    .parse::<Channel>(&data_dir, &target.dir, &validators, &|_| true)
+                    .await?;
+            }
+            Model::BlockedEdge => {
+                checks
+                    .parse::<BlockedEdge>(&data_dir, &target.dir, &validators, &|_| true)
+                    .await?;
+            }
+        }
+    }
+
+    let versions = || {
+        blocked_edge_vec
+            .iter()
+            .map(|(path, edge)| (path.as_path(), &edge.to))
+            .chain(channels_vec.iter().flat_map(|(path, channel)| {
+                channel.versions.iter().map(move |v| (path.as_path(), v))
+            }))
+    };
+
+    let bounds = Bounds {
+        major: (opts.min_major, opts.max_major),
+        max_minor: opts.max_minor,
+        max_patch: opts.max_patch,
+    };
+    // Channels which weren't parsed would appear to be missing
+    let all_channels = opts.channels.is_empty() && !opts.staged;
+
+    for descriptor in checks::CHECKS.iter() {
+        match descriptor.check {
+            // Run while parsing, and by check_releases
+            Check::YamlSyntax | Check::RegistryPresence => {}
+            Check::BuildMetadata => checks.error(
+                descriptor.check,
+                "Found versions with unrecognized build metadata",
+                || check_build_metadata(versions(), &opts.arches),
+            )?,
+            Check::VersionBounds => checks.warning(
+                descriptor.check,
+                "Found versions with implausible components",
+                || check_bounds(versions(), &bounds),
+            )?,
+            Check::BlockedEdgeArch => {
+                if let Some(convention) = opts.blocked_edge_arch {
+                    checks.error(
+                        descriptor.check,
+                        "Found non-canonical blocked edge versions",
+                        || check_blocked_edge_arch(&blocked_edge_vec, convention, &opts.arches),
+                    )?;
+                }
+            }
+            Check::DuplicateEdges => checks.warning(
+                descriptor.check,
+                "Found blocked edges duplicating each other",
+                || channels::check_duplicate_edges(&blocked_edge_vec),
+            )?,
+            Check::ChannelMembership => {
+                if all_channels {
+                    checks.warning(
+                        descriptor.check,
+                        "Found blocked edges to versions which no channel lists",
+                        || channels::check_membership(&channels_vec, &blocked_edge_vec),
+                    )?;
+                }
+            }
+            Check::ChannelNames => checks.error(
+                descriptor.check,
+                "Found channels named differently from their file",
+                || channels::check_names(&channels_vec),
+            )?,
+            Check::DuplicateVersions => checks.error(
+                descriptor.check,
+                "Found duplicate versions in channels",
+                || channels::check_duplicate_versions(&channels_vec),
+            )?,
+            Check::SortedVersions => {
+                if opts.fix_sorted {
+                    for (path, channel) in channels_vec.iter() {
+                        if channels::is_sorted(channel) {
+                            continue;
+                        }
+                        let text = tokio::fs::read_to_string(path).await?;
+                        let sorted = channels::sort_versions_text(&text)
+                            .context(format!("Sorting versions in {:?}", path))?;
+                        tokio::fs::write(path, sorted).await?;
+                        info!("Sorted versions in {:?}", path);
+                        report.finding(
+                            SUITE,
+                            descriptor.name,
+                            Severity::Info,
+                            Problem::new(format!("{:?}: sorted versions", path)).in_file(path),
+                        );
+                    }
+                } else if opts.check_sorted {
+                    checks.error(
+                        descriptor.check,
+                        "Found channels with unsorted versions",
+                        || channels::check_sorted(&channels_vec),
+                    )?;
+                }
+            }
+            Check::FeederChannels => {
+                if all_channels {
+                    checks.warning(
+                        descriptor.check,
+                        "Found channels without a channel to be promoted from",
+                        || channels::check_feeders(&channels_vec, &opts.tiers),
+                    )?;
+                }
+            }
+            Check::PromotionFunnel => checks.error(
+                descriptor.check,
+                "Found versions which skipped a channel tier",
+                || channels::check_promotion_funnel(&channels_vec, &opts.tiers),
+            )?,
+            Check::DeadEnds => checks.error(
+                descriptor.check,
+                "Found versions left without an update",
+                || {
+                    channels::check_dead_ends(
+                        &channels_vec,
+                        &blocked_edge_vec,
+                        &opts.terminal_versions,
+                    )
+                },
+            )?,
+            Check::SupersededPrereleases => checks.warning(
+                descriptor.check,
+                "Found pre-releases whose GA version is available",
+                || channels::check_superseded_prereleases(&channels_vec, &found_versions.all()),
+            )?,
+            Check::Baseline => {
+                let path = match &opts.baseline {
+                    Some(path) => path,
+                    None => continue,
+                };
+                let started = match checks.start(descriptor.check) {
+                    Some(started) => started,
+                    None => continue,
+                };
+                info!("Comparing found versions against {:?}", path);
+                let result = match baseline::load(path).await {
+                    Ok(expected) => {
+                        let diff = baseline::diff(&expected, &found_versions.all());
+                        if opts.baseline_additions {
+                            for version in diff.added.iter() {
+                                info!("added: {}", version);
+                                report.finding(
+                                    SUITE,
+                                    descriptor.name,
+                                    Severity::Info,
+                                    Problem::new(format!("{} was added", version)).about(version),
+                                );
+                            }
+                        }
+                        for version in diff.removed.iter() {
+                            info!("removed: {}", version);
+                        }
+                        let errors = if opts.no_removals {
+                            diff.removed
+                                .iter()
+                                .map(|version| {
+                                    Problem::new(format!("{} was removed", version)).about(version)
+                                })
+                                .collect()
+                        } else {
+                            vec![]
+                        };
+                        check(
+                            report,
+                            descriptor.name,
+                            "Found versions removed since the baseline",
+                            errors,
+                        )
+                    }
+                    Err(e) => Err(e),
+                };
+                checks.finish(descriptor.check, started, result)?;
+            }
+            Check::MonotonicChannels => {
+                let (rev, tier) = match (&opts.monotonic_since, opts.tiers.last()) {
+                    (Some(rev), Some(tier)) => (rev, tier),
+                    _ => continue,
+                };
+                let started = match checks.start(descriptor.check) {
+                    Some(started) => started,
+                    None => continue,
+                };
+                info!("Comparing {} channels against {}", tier, rev);
+                let result = history::check_monotonic(&channels_vec, &blocked_edge_vec, rev, tier)
+                    .and_then(|errors| {
+                        check(
+                            report,
+                            descriptor.name,
+                            "Found versions silently dropped from channels",
+                            checks.lines.locate(errors),
+                        )
+                    });
+                checks.finish(descriptor.check, started, result)?;
+            }
         }
     }
+    checks.into_result()?;
+
+    selection.retain_staged(&mut found_versions, &channels_vec, &blocked_edge_vec);
+    selection.retain_new(&mut found_versions, &channels_vec, &blocked_edge_vec)?;
+    found_versions.retain_selected(opts);
 
     Ok(found_versions)
 }
+
+/// The directories below the data directory which hold files to validate.
+pub fn data_dirs(opts: &Options) -> Vec<PathBuf> {
+    [plugin::CHANNELS_DIR, plugin::BLOCKED_EDGES_DIR]
+        .iter()
+        .map(|name| opts.data_dir.join(name))
+        .chain(
+            opts.extra_targets
+                .iter()
+                .map(|target| opts.data_dir.join(&target.dir)),
+        )
+        .collect()
+}
+
+/// The parsed channel and blocked edge files, without any of the checks `run` performs on them.
+///
+/// Only the files `selection` keeps are parsed, or all of them if there is none.
+async fn load(
+    opts: &Options,
+    data_dir: &Path,
+    selection: Option<&Selection<'_>>,
+) -> Fallible<(Vec<(PathBuf, Channel)>, Vec<(PathBuf, BlockedEdge)>)> {
+    let channel_path = data_subdir(data_dir, Path::new(plugin::CHANNELS_DIR)).await?;
+    let channels_vec = walk_files::<Channel>(
+        &channel_path,
+        &Model::Channel.validators(opts.deny_unknown_fields, &Custom::default()),
+        &|path| selection.map_or(true, |selection| selection.keeps_channel(path)),
+    )
+    .await?
+    .into_result()?;
+    let blocked_edge_path = data_subdir(data_dir, Path::new(plugin::BLOCKED_EDGES_DIR)).await?;
+    let blocked_edge_vec = walk_files::<BlockedEdge>(
+        &blocked_edge_path,
+        &Model::BlockedEdge.validators(opts.deny_unknown_fields, &Custom::default()),
+        &|_| true,
+    )
+    .await?
+    .into_result()?;
+    Ok((channels_vec, blocked_edge_vec))
+}
+
+fn canonical_data_dir(opts: &Options) -> Fallible<PathBuf> {
+    opts.data_dir
+        .canonicalize()
+        .context(format!("Resolving data directory {:?}", opts.data_dir))
+}
+
+/// The versions `run` would return, skipping its checks of the graph data.
+pub async fn collect(opts: &Options) -> Fallible<FoundVersions> {
+    let data_dir = canonical_data_dir(opts)?;
+    let selection = Selection::new(opts, &data_dir)?;
+    let (channels_vec, blocked_edge_vec) = load(opts, &data_dir, Some(&selection)).await?;
+    check_channels_found(opts, &channels_vec)?;
+    let mut found_versions = FoundVersions::default();
+    for (_, edge) in blocked_edge_vec.iter() {
+        found_versions.blocked_edges.insert(edge.to.clone());
+    }
+    for (_, channel) in channels_vec.iter() {
+        found_versions
+            .channels
+            .extend(channel.versions.iter().cloned());
+    }
+    selection.retain_staged(&mut found_versions, &channels_vec, &blocked_edge_vec);
+    selection.retain_new(&mut found_versions, &channels_vec, &blocked_edge_vec)?;
+    found_versions.retain_selected(opts);
+    Ok(found_versions)
+}
+
+/// A CSV of the parsed channels with a row per version and architecture, marking the channels
+/// listing it with `x`.
+pub async fn matrix(opts: &Options) -> Fallible<String> {
+    let data_dir = canonical_data_dir(opts)?;
+    let selection = Selection::new(opts, &data_dir)?;
+    let (channels_vec, _) = load(opts, &data_dir, Some(&selection)).await?;
+    check_channels_found(opts, &channels_vec)?;
+
+    // Versions without their build metadata, which holds the architecture
+    let mut rows: BTreeMap<(Version, String), Vec<bool>> = BTreeMap::new();
+    for (i, (_, channel)) in channels_vec.iter().enumerate() {
+        for version in channel.versions.iter() {
+            let mut release = version.clone();
+            release.build.clear();
+            let arch = version
+                .build
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(".");
+            rows.entry((release, arch))
+                .or_insert_with(|| vec![false; channels_vec.len()])[i] = true;
+        }
+    }
+
+    let mut csv = String::from("version,arch");
+    for (_, channel) in channels_vec.iter() {
+        csv.push(',');
+        csv.push_str(&channel.name);
+    }
+    csv.push('\n');
+    for ((version, arch), listed) in rows.iter() {
+        csv.push_str(&format!("{},{}", version, arch));
+        for listed in listed.iter() {
+            csv.push_str(if *listed { ",x" } else { "," });
+        }
+        csv.push('\n');
+    }
+    Ok(csv)
+}
+
+/// Print each channel listing `version` and each blocked edge mentioning it.
+///
+/// Unlike `run`, this ignores `--since` and `--filter-version`, only noting whether the version
+/// would be filtered.
+pub async fn explain(opts: &Options, version: &Version) -> Fallible<()> {
+    let (channels_vec, blocked_edge_vec) = load(opts, &canonical_data_dir(opts)?, None).await?;
+
+    println!("Explaining {}", version);
+    let mut listed = false;
+    for (path, channel) in channels_vec.iter() {
+        for v in channel.versions.iter().filter(|v| *v == version) {
+            println!("  listed as {} in {} ({:?})", v, channel.name, path);
+            listed = true;
+        }
+    }
+    if !listed {
+        println!("  not listed in any channel");
+    }
+    let from = version.to_string();
+    for (path, edge) in blocked_edge_vec.iter() {
+        if edge.to == *version {
+            println!(
+                "  updates from '{}' to {} are blocked by {:?}",
+                edge.from, edge.to, path
+            );
+        }
+        if edge.from.is_match(&from) {
+            println!("  updates from it to {} are blocked by {:?}", edge.to, path);
+        }
+    }
+    if !is_version_selected(opts, version) {
+        println!("  excluded by --since or --filter-version, so skipped by the registry check");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validators::ValidationError;
+    use structopt::StructOpt;
+
+    /// A data directory below the system's temporary directory holding `files`, relative to it.
+    fn fixture(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "cincinnati-graph-data-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        for dir_name in [plugin::CHANNELS_DIR, plugin::BLOCKED_EDGES_DIR].iter() {
+            std::fs::create_dir_all(dir.join(dir_name)).unwrap();
+        }
+        for (path, contents) in files.iter() {
+            std::fs::write(dir.join(path), contents).unwrap();
+        }
+        dir
+    }
+
+    fn options(data_dir: &Path, args: &[&str]) -> Options {
+        let data_dir = data_dir.to_string_lossy().into_owned();
+        let mut argv = vec![
+            "cincinnati-graph-data",
+            "--offline",
+            "--data-dir",
+            data_dir.as_str(),
+        ];
+        argv.extend_from_slice(args);
+        Options::from_iter(&argv)
+    }
+
+    /// Requires an `owner` key, as a team might to know whom to ask about a channel.
+    struct RequiresOwner;
+
+    impl FileValidator for RequiresOwner {
+        fn validate(&self, path: &Path, value: &Value) -> Result<(), Vec<ValidationError>> {
+            let owner = Value::String("owner".to_string());
+            match value.as_mapping() {
+                Some(mapping) if mapping.contains_key(&owner) => Ok(()),
+                _ => Err(vec![ValidationError {
+                    message: format!("{:?} has no owner", path.file_name().unwrap()),
+                }]),
+            }
+        }
+    }
+
+    #[test]
+    fn custom_validators_reject_files() {
+        let dir = fixture(
+            "custom-validators",
+            &[
+                (
+                    "channels/stable-4.5.yaml",
+                    "name: stable-4.5\nowner: updates\nversions:\n- 4.5.1\n",
+                ),
+                (
+                    "channels/fast-4.5.yaml",
+                    "name: fast-4.5\nversions:\n- 4.5.1\n",
+                ),
+                ("blocked-edges/4.5.1.yaml", "to: 4.5.1\nfrom: 4\\.4\\..*\n"),
+            ],
+        );
+        let opts = options(&dir, &["--checks", "yaml-syntax"]);
+        let custom = Custom {
+            channels: vec![Box::new(RequiresOwner)],
+            blocked_edges: vec![],
+        };
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let report = Report::default();
+        let e = runtime.block_on(run(&opts, &report, &custom)).unwrap_err();
+        let message = format!("{:#}", e);
+        assert!(
+            message.contains("\"fast-4.5.yaml\" has no owner"),
+            "{}",
+            message
+        );
+        assert!(
+            !message.contains("stable-4.5.yaml\" has no owner"),
+            "{}",
+            message
+        );
+
+        // Without them, the same files pass
+        let report = Report::default();
+        runtime
+            .block_on(run(&opts, &report, &Custom::default()))
+            .unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn scan_notes_the_line_of_each_scalar() {
+        let contents = b"name: fast-4.5\nversions:\n- 4.5.1\n- 4.5.2\n";
+        let (lines, _) = scan(Path::new("fast-4.5.yaml"), contents).unwrap();
+        assert_eq!(lines.get("fast-4.5"), Some(&1));
+        assert_eq!(lines.get("4.5.2"), Some(&4));
+    }
+
+    #[test]
+    fn scan_rejects_repeated_keys() {
+        let contents = b"name: fast-4.5\nversions:\n- 4.5.1\nversions:\n- 4.5.2\n";
+        let problem = scan(Path::new("fast-4.5.yaml"), contents).unwrap_err();
+        assert_eq!(problem.line, Some(4));
+        assert!(problem.message.ends_with("line 4: key 'versions'"));
+
+        // The same key in different mappings is no repetition
+        let contents = b"a:\n  name: x\nb:\n  name: y\n";
+        assert!(scan(Path::new("nested.yaml"), contents).is_ok());
+    }
+
+    #[test]
+    fn scan_notes_merge_keys() {
+        let contents = b"base: &base\n  name: fast-4.5\nchannel:\n  <<: *base\n  versions: []\n";
+        let (_, merges) = scan(Path::new("fast-4.5.yaml"), contents).unwrap();
+        assert_eq!(merges, vec![4]);
+    }
+
+    #[test]
+    fn merge_keys_lose_to_the_keys_beside_them() {
+        let value: Value = serde_yaml::from_str(
+            "a: &a {x: 1, y: 1}\nb: &b {y: 2, z: 2}\nc:\n  <<: [*a, *b]\n  x: 3\n",
+        )
+        .unwrap();
+        let expected: Value =
+            serde_yaml::from_str("a: {x: 1, y: 1}\nb: {y: 2, z: 2}\nc: {x: 3, y: 1, z: 2}\n")
+                .unwrap();
+        assert_eq!(expand_merges(value).unwrap(), expected);
+
+        let value: Value = serde_yaml::from_str("a:\n  <<: 1\n").unwrap();
+        assert!(expand_merges(value).is_err());
+    }
+
+    #[test]
+    fn parse_file_resolves_aliases_and_merge_keys() {
+        let dir = fixture(
+            "parse-file-aliases",
+            &[
+                (
+                    "channels/fast-4.5.yaml",
+                    "x-versions: &versions\n- 4.5.1\n- 4.5.2\nname: fast-4.5\nversions: *versions\n",
+                ),
+                (
+                    "blocked-edges/4.5.2.yaml",
+                    "x-edge: &edge\n  to: 4.5.1\n  from: 4\\.4\\..*\n<<: *edge\nto: 4.5.2\n",
+                ),
+            ],
+        );
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let validators = Model::Channel.validators(false, &Custom::default());
+        let (channel, _, merge) = runtime
+            .block_on(parse_file::<Channel>(
+                &dir.join("channels/fast-4.5.yaml"),
+                false,
+                &validators,
+            ))
+            .unwrap();
+        assert_eq!(channel.name, "fast-4.5");
+        let versions: Vec<String> = channel.versions.iter().map(ToString::to_string).collect();
+        assert_eq!(versions, vec!["4.5.1", "4.5.2"]);
+        assert_eq!(merge, None);
+
+        let validators = Model::BlockedEdge.validators(false, &Custom::default());
+        let (edge, _, merge) = runtime
+            .block_on(parse_file::<BlockedEdge>(
+                &dir.join("blocked-edges/4.5.2.yaml"),
+                false,
+                &validators,
+            ))
+            .unwrap();
+        assert_eq!(edge.to, Version::parse("4.5.2").unwrap());
+        assert_eq!(edge.from.as_str(), "4\\.4\\..*");
+        assert_eq!(merge, Some(4));
+
+        // The loader doesn't expand the merge key, which is put down to the file using it
+        let parsed = runtime
+            .block_on(walk_files::<BlockedEdge>(
+                &dir.join(plugin::BLOCKED_EDGES_DIR),
+                &validators,
+                &|_| true,
+            ))
+            .unwrap();
+        assert_eq!(parsed.problems.len(), 1);
+        assert_eq!(
+            parsed.problems[0].file,
+            Some(dir.join("blocked-edges/4.5.2.yaml"))
+        );
+        assert_eq!(parsed.problems[0].line, Some(4));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn aliases_to_undefined_anchors_are_named() {
+        let dir = fixture(
+            "parse-file-anchors",
+            &[(
+                "channels/fast-4.5.yaml",
+                "name: fast-4.5\nversions: *versions\n",
+            )],
+        );
+        let validators = Model::Channel.validators(false, &Custom::default());
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let problem = runtime
+            .block_on(parse_file::<Channel>(
+                &dir.join("channels/fast-4.5.yaml"),
+                false,
+                &validators,
+            ))
+            .map(|_| ())
+            .unwrap_err();
+        assert!(problem
+            .message
+            .contains("uses an alias to an undefined anchor"));
+        assert_eq!(problem.line, Some(2));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn with_snippet_points_at_the_error() {
+        let contents = b"name: fast-4.5\nversions: ]\n";
+        let e = serde_yaml::from_slice::<Value>(contents).unwrap_err();
+        let message = with_snippet(e, contents).to_string();
+        let lines: Vec<&str> = message.lines().collect();
+        assert_eq!(lines[1], " 2 | versions: ]");
+        assert!(lines[2].starts_with("   | ") && lines[2].ends_with('^'));
+    }
+
+    #[test]
+    fn walk_files_reports_every_invalid_file() {
+        let dir = fixture(
+            "walk-files",
+            &[
+                (
+                    "channels/candidate-4.5.yaml",
+                    "name: candidate-4.5\nversions: ]\n",
+                ),
+                (
+                    "channels/fast-4.5.yaml",
+                    "name: fast-4.5\nversions:\n- 4.5.1\n",
+                ),
+                (
+                    "channels/stable-4.5.yaml",
+                    "name: stable-4.5\nversions: []\nversions: []\n",
+                ),
+            ],
+        );
+        let validators = Model::Channel.validators(false, &Custom::default());
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let parsed = runtime
+            .block_on(walk_files::<Channel>(
+                &dir.join(plugin::CHANNELS_DIR),
+                &validators,
+                &|_| true,
+            ))
+            .unwrap();
+
+        assert_eq!(parsed.files.len(), 1);
+        assert!(parsed.files[0].0.ends_with("fast-4.5.yaml"));
+        let files: Vec<PathBuf> = parsed
+            .problems
+            .iter()
+            .map(|problem| problem.file.clone().unwrap())
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                dir.join("channels/candidate-4.5.yaml"),
+                dir.join("channels/stable-4.5.yaml")
+            ]
+        );
+        assert_eq!(parsed.problems[0].line, Some(2));
+        assert_eq!(parsed.problems[1].line, Some(3));
+        assert!(parsed.into_result().is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn walk_files_ignores_files_left_out_of_the_selection() {
+        let dir = fixture(
+            "walk-files-selection",
+            &[
+                (
+                    "channels/candidate-4.5.yaml",
+                    "name: candidate-4.5\nversions: ]\n",
+                ),
+                (
+                    "channels/stable-4.5.yaml",
+                    "name: stable-4.5\nversions:\n- 4.5.1\n",
+                ),
+            ],
+        );
+        let validators = Model::Channel.validators(false, &Custom::default());
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let parsed = runtime
+            .block_on(walk_files::<Channel>(
+                &dir.join(plugin::CHANNELS_DIR),
+                &validators,
+                &|path| path.ends_with("stable-4.5.yaml"),
+            ))
+            .unwrap();
+
+        assert_eq!(parsed.files.len(), 1);
+        assert!(parsed.problems.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn walk_files_applies_the_validators() {
+        let dir = fixture(
+            "walk-files-validators",
+            &[(
+                "channels/fast-4.5.yaml",
+                "name: fast-4.5\nversions: []\nverisons: []\n",
+            )],
+        );
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        for &deny_unknown_fields in [false, true].iter() {
+            let validators = Model::Channel.validators(deny_unknown_fields, &Custom::default());
+            let parsed = runtime
+                .block_on(walk_files::<Channel>(
+                    &dir.join(plugin::CHANNELS_DIR),
+                    &validators,
+                    &|_| true,
+                ))
+                .unwrap();
+            if deny_unknown_fields {
+                assert!(parsed.files.is_empty());
+                assert!(parsed.problems[0].message.contains("verisons"));
+            } else {
+                assert_eq!(parsed.files.len(), 1);
+                assert!(parsed.problems.is_empty());
+            }
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn selection_keeps_the_selected_channels() {
+        let opts = options(
+            Path::new("/srv/graph-data"),
+            &["--channel", "stable-4.5,stable-4.6"],
+        );
+        let channels = Path::new("/srv/graph-data/channels");
+        let selection = Selection {
+            opts: &opts,
+            staged: None,
+        };
+        assert!(selection.keeps_channel(&channels.join("stable-4.5.yaml")));
+        assert!(!selection.keeps_channel(&channels.join("fast-4.5.yaml")));
+    }
+
+    #[test]
+    fn selection_keeps_the_other_tiers_of_staged_minors() {
+        let opts = options(Path::new("/srv/graph-data"), &[]);
+        let channels = Path::new("/srv/graph-data/channels");
+        let selection = Selection {
+            opts: &opts,
+            staged: Some(vec![channels.join("fast-4.5.yaml")].into_iter().collect()),
+        };
+        assert!(selection.keeps_channel(&channels.join("stable-4.5.yaml")));
+        assert!(!selection.keeps_channel(&channels.join("stable-4.6.yaml")));
+    }
+
+    #[test]
+    fn version_filters_match_globs_and_ranges() {
+        let version = |v| Version::parse(v).unwrap();
+        let glob: VersionFilter = "4.5.*".parse().unwrap();
+        assert!(glob.matches(&version("4.5.1")));
+        assert!(!glob.matches(&version("4.50.1")));
+        let range: VersionFilter = ">=4.5, <4.7".parse().unwrap();
+        assert!(range.matches(&version("4.6.3")));
+        assert!(!range.matches(&version("4.7.0")));
+        assert!("4.5".parse::<VersionFilter>().is_ok());
+        assert!("not a range".parse::<VersionFilter>().is_err());
+    }
+
+    fn blocked_edges(versions: &[&str]) -> Vec<(PathBuf, BlockedEdge)> {
+        versions
+            .iter()
+            .map(|to| {
+                let yaml = format!("to: {}\nfrom: '.*'\n", to);
+                (
+                    PathBuf::from(format!("blocked-edges/{}.yaml", to)),
+                    serde_yaml::from_str(&yaml).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn arches() -> Vec<String> {
+        vec!["amd64".to_string(), "s390x".to_string()]
+    }
+
+    #[test]
+    fn blocked_edges_may_be_required_to_omit_the_arch() {
+        let edges = blocked_edges(&["4.5.1", "4.5.2+amd64", "4.5.3-s390x"]);
+        let problems = check_blocked_edge_arch(&edges, ArchConvention::Omit, &arches());
+        let versions: Vec<String> = problems
+            .iter()
+            .map(|problem| problem.version.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(versions, vec!["4.5.2+amd64", "4.5.3-s390x"]);
+        assert!(problems[0]
+            .message
+            .ends_with("'4.5.2+amd64' should not have an architecture suffix"));
+        assert_eq!(
+            problems[0].file,
+            Some(PathBuf::from("blocked-edges/4.5.2+amd64.yaml"))
+        );
+        assert!(problems[1].message.contains("use '+<arch>' instead"));
+    }
+
+    #[test]
+    fn blocked_edges_may_be_required_to_spell_the_arch() {
+        let edges = blocked_edges(&["4.5.1", "4.5.2+amd64", "4.5.3-s390x"]);
+        let problems = check_blocked_edge_arch(&edges, ArchConvention::Require, &arches());
+        let versions: Vec<String> = problems
+            .iter()
+            .map(|problem| problem.version.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(versions, vec!["4.5.1", "4.5.3-s390x"]);
+        assert!(problems[0]
+            .message
+            .ends_with("'4.5.1' is missing an architecture suffix"));
+    }
+
+    #[test]
+    fn blocked_edge_arch_convention_is_chosen_on_the_command_line() {
+        let data_dir = Path::new("/srv/graph-data");
+        assert_eq!(options(data_dir, &[]).blocked_edge_arch, None);
+        let opts = options(data_dir, &["--blocked-edge-arch", "require"]);
+        assert_eq!(opts.blocked_edge_arch, Some(ArchConvention::Require));
+        assert!("drop".parse::<ArchConvention>().is_err());
+    }
+
+    #[test]
+    fn extra_targets_are_validated_with_their_model() {
+        let dir = fixture(
+            "extra-targets",
+            &[
+                (
+                    "channels/fast-4.5.yaml",
+                    "name: fast-4.5\nversions:\n- 4.5.1\n",
+                ),
+                ("blocked-edges/4.5.1.yaml", "to: 4.5.1\nfrom: 4\\.4\\..*\n"),
+            ],
+        );
+        std::fs::create_dir_all(dir.join("raw-metadata")).unwrap();
+        std::fs::write(
+            dir.join("raw-metadata/fast-4.6.yaml"),
+            "name: fast-4.6\nversions:\n- 4.6.1\n",
+        )
+        .unwrap();
+        let opts = options(
+            &dir,
+            &[
+                "--checks",
+                "yaml-syntax",
+                "--validate-dir",
+                "raw-metadata=channel",
+            ],
+        );
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(run(&opts, &Report::default(), &Custom::default()))
+            .unwrap();
+
+        // The same file isn't a blocked edge
+        let opts = options(
+            &dir,
+            &[
+                "--checks",
+                "yaml-syntax",
+                "--validate-dir",
+                "raw-metadata=blocked-edge",
+            ],
+        );
+        let e = runtime
+            .block_on(run(&opts, &Report::default(), &Custom::default()))
+            .unwrap_err();
+        assert!(format!("{:#}", e).contains("raw-metadata/fast-4.6.yaml"));
+
+        let e = "raw-metadata=release".parse::<Target>().unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "unknown model 'release', expected 'channel' or 'blocked-edge'"
+        );
+        assert!("raw-metadata".parse::<Target>().is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn build_metadata_must_be_a_known_arch() {
+        let path = Path::new("channels/fast-4.11.yaml");
+        let versions: Vec<Version> = ["4.11.0+arm64", "4.11.0+wat", "4.11.1", "4.11.2+arm64.1"]
+            .iter()
+            .map(|v| Version::parse(v).unwrap())
+            .collect();
+        let arches = vec!["amd64".to_string(), "arm64".to_string()];
+        let problems = check_build_metadata(versions.iter().map(|v| (path, v)), &arches);
+
+        assert_eq!(problems.len(), 2);
+        assert_eq!(
+            problems[0].message,
+            "\"channels/fast-4.11.yaml\": '4.11.0+wat' has unrecognized build metadata 'wat'"
+        );
+        assert_eq!(problems[0].file, Some(path.to_path_buf()));
+        assert!(problems[1].message.ends_with("build metadata 'arm64.1'"));
+
+        // The recognized arches are configurable
+        let problems = check_build_metadata(
+            versions[..1].iter().map(|v| (path, v)),
+            &["amd64".to_string()],
+        );
+        assert_eq!(problems.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_followed_but_reported_by_their_name() {
+        let dir = fixture(
+            "symlinks",
+            &[(
+                "channels/stable-4.14.yaml",
+                "name: stable-4.14\nversions:\n- 4.14.1\n",
+            )],
+        );
+        let channels = dir.join(plugin::CHANNELS_DIR);
+        let link = |target: &str, name: &str| {
+            std::os::unix::fs::symlink(target, channels.join(name)).unwrap();
+        };
+        link("stable-4.14.yaml", "stable-4.latest.yaml");
+        link("missing.yaml", "fast-4.14.yaml");
+        link("loop-b.yaml", "loop-a.yaml");
+        link("loop-a.yaml", "loop-b.yaml");
+        let validators = Model::Channel.validators(false, &Custom::default());
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let parsed = runtime
+            .block_on(walk_files::<Channel>(&channels, &validators, &|_| true))
+            .unwrap();
+
+        let files: Vec<PathBuf> = parsed.files.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(
+            files,
+            vec![
+                channels.join("stable-4.14.yaml"),
+                channels.join("stable-4.latest.yaml")
+            ]
+        );
+        assert_eq!(parsed.files[1].1.name, "stable-4.14");
+        assert_eq!(parsed.problems.len(), 3);
+        assert_eq!(
+            parsed.problems[0].file,
+            Some(channels.join("fast-4.14.yaml"))
+        );
+        assert!(parsed.problems[0]
+            .message
+            .ends_with("fast-4.14.yaml\" is a dangling symlink"));
+        assert_eq!(parsed.problems[1].file, Some(channels.join("loop-a.yaml")));
+        assert!(parsed.problems[1].message.contains("is it a symlink loop?"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn since_drops_the_versions_of_older_minors() {
+        let versions = |versions: &[&str]| -> HashSet<Version> {
+            versions
+                .iter()
+                .map(|v| Version::parse(v).unwrap())
+                .collect()
+        };
+        let mut found_versions = FoundVersions {
+            channels: versions(&["4.9.5+amd64", "4.10.0-rc.1", "4.10.0", "4.11.2+s390x"]),
+            blocked_edges: versions(&["4.9.4", "4.10.3"]),
+        };
+        let opts = options(Path::new("/srv/graph-data"), &["--since", "4.10"]);
+        found_versions.retain_selected(&opts);
+
+        let mut kept: Vec<String> = found_versions
+            .all()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        kept.sort();
+        assert_eq!(
+            kept,
+            vec!["4.10.0", "4.10.0-rc.1", "4.10.3", "4.11.2+s390x"]
+        );
+    }
+
+    #[test]
+    fn problems_are_reported_in_a_stable_order() {
+        let broken = "name: x\nversions: ]\n";
+        let dir = fixture(
+            "stable-order",
+            &[
+                ("channels/stable-4.6.yaml", broken),
+                ("channels/candidate-4.7.yaml", broken),
+                ("channels/fast-4.5.yaml", broken),
+            ],
+        );
+        let validators = Model::Channel.validators(false, &Custom::default());
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut walk = || -> Vec<PathBuf> {
+            let parsed = runtime
+                .block_on(walk_files::<Channel>(
+                    &dir.join(plugin::CHANNELS_DIR),
+                    &validators,
+                    &|_| true,
+                ))
+                .unwrap();
+            parsed
+                .problems
+                .into_iter()
+                .map(|problem| problem.file.unwrap())
+                .collect()
+        };
+        let first = walk();
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert_eq!(first, sorted);
+        for _ in 0..3 {
+            assert_eq!(walk(), first);
+        }
+
+        let report = Report::default();
+        let problems = vec![Problem::new("b: second"), Problem::new("a: first")];
+        let e = check(&report, "dead-ends", "Found dead ends", problems).unwrap_err();
+        assert_eq!(e.to_string(), "Found dead ends:\na: first\nb: second");
+        let findings: Vec<String> = report
+            .summary()
+            .findings
+            .iter()
+            .map(|finding| finding.message.clone())
+            .collect();
+        assert_eq!(findings, vec!["a: first", "b: second"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn data_subdirs_must_exist_and_hold_yaml_files() {
+        let dir = fixture(
+            "data-subdirs",
+            &[("channels/README.md", "Channels of the update graph\n")],
+        );
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut subdir = |name: &str| {
+            runtime
+                .block_on(data_subdir(&dir, Path::new(name)))
+                .map_err(|e| e.to_string())
+        };
+
+        let e = subdir("channels").unwrap_err();
+        assert!(e.starts_with("\"channels\" directory at "), "{}", e);
+        assert!(e.ends_with(" contains no YAML files"), "{}", e);
+        let e = subdir("blocked-edges").unwrap_err();
+        assert!(e.ends_with(" contains no YAML files"), "{}", e);
+        std::fs::remove_dir_all(dir.join(plugin::BLOCKED_EDGES_DIR)).unwrap();
+        let e = subdir("blocked-edges").unwrap_err();
+        assert_eq!(
+            e,
+            format!(
+                "\"blocked-edges\" directory not found at {:?}",
+                dir.join("blocked-edges")
+            )
+        );
+
+        std::fs::write(dir.join("channels/fast-4.5.yaml"), "name: fast-4.5\n").unwrap();
+        assert_eq!(
+            subdir("channels").unwrap(),
+            dir.join("channels").canonicalize().unwrap()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bounds_flag_implausible_components() {
+        let path = Path::new("channels/fast-4.11.yaml");
+        let versions: Vec<Version> = [
+            "3.11.0",
+            "9.99.999",
+            "4.11.0",
+            "2.11.0",
+            "44.11.0",
+            "4.110.0",
+            "4.11.1000",
+        ]
+        .iter()
+        .map(|v| Version::parse(v).unwrap())
+        .collect();
+        let bounds = Bounds {
+            major: (3, 9),
+            max_minor: 99,
+            max_patch: 999,
+        };
+        let problems = check_bounds(versions.iter().map(|v| (path, v)), &bounds);
+
+        let flagged: Vec<String> = problems
+            .iter()
+            .map(|problem| problem.version.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(flagged, vec!["2.11.0", "44.11.0", "4.110.0", "4.11.1000"]);
+        assert_eq!(
+            problems[1].message,
+            "\"channels/fast-4.11.yaml\": '44.11.0' looks implausible"
+        );
+        assert_eq!(problems[1].file, Some(path.to_path_buf()));
+    }
+}
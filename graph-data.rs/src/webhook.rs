@@ -0,0 +1,94 @@
+//! A notification posted to a webhook like Slack's when a run fails.
+
+use crate::report::{PhaseStatus, RunReport, Severity};
+use anyhow::Result as Fallible;
+use semver::Version;
+use serde_json::json;
+use std::collections::BTreeSet;
+use tracing::info;
+
+/// Post a summary of the failed checks and versions to `url`.
+///
+/// The summary is in `text`, as Slack incoming webhooks expect, with the same details as lists in
+/// `failed_checks` and `failed_versions` for other receivers.
+pub async fn notify(client: &reqwest::Client, url: &str, summary: &RunReport) -> Fallible<()> {
+    let mut failed_checks: Vec<String> = summary
+        .checks
+        .iter()
+        .filter(|check| check.status() == "fail")
+        .map(|check| check.name.clone())
+        .collect();
+    // Phases can fail without a check of their own failing, e.g. on a file which doesn't parse
+    for phase in summary.phases.iter() {
+        let has_failed_check = summary
+            .checks
+            .iter()
+            .any(|check| check.suite == phase.name && check.status() == "fail");
+        if phase.status == PhaseStatus::Failed && !has_failed_check {
+            failed_checks.push(phase.name.to_string());
+        }
+    }
+    let failed_versions: BTreeSet<&Version> = summary
+        .findings
+        .iter()
+        .filter(|finding| finding.severity == Severity::Error)
+        .filter_map(|finding| finding.version.as_ref())
+        .collect();
+    let failed_versions: Vec<String> = failed_versions.iter().map(ToString::to_string).collect();
+
+    let mut text = format!(
+        "Graph data checks failed: {}",
+        if failed_checks.is_empty() {
+            "no check failed".to_string()
+        } else {
+            failed_checks.join(", ")
+        }
+    );
+    if !failed_versions.is_empty() {
+        text.push_str(&format!("\nVersions: {}", failed_versions.join(", ")));
+    }
+    let body = json!({
+        "text": text,
+        "failed_checks": failed_checks,
+        "failed_versions": failed_versions,
+    });
+    let host = host(url);
+    client
+        .post(url)
+        .json(&body)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        // reqwest errors include the URL, which holds the webhook's secret
+        .map_err(|e| match e.status() {
+            Some(status) => anyhow::anyhow!("Notifying the webhook at {} failed: {}", host, status),
+            None if e.is_timeout() => {
+                anyhow::anyhow!("Notifying the webhook at {} timed out", host)
+            }
+            None => anyhow::anyhow!("Notifying the webhook at {} failed to connect", host),
+        })?;
+    info!("Notified the webhook at {} of the failure", host);
+    Ok(())
+}
+
+/// The host of the webhook `url`, the only part of it which is safe to log.
+fn host(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(String::from))
+        .unwrap_or_else(|| "an invalid URL".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_host_is_logged() {
+        assert_eq!(
+            host("https://hooks.slack.com/services/T000/B000/secret"),
+            "hooks.slack.com"
+        );
+        assert_eq!(host("hooks.slack.com/services/secret"), "an invalid URL");
+    }
+}